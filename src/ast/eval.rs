@@ -1,6 +1,7 @@
 use std::{collections::HashMap};
 
 use crate::ast::{Node, Tree};
+use crate::lexer::Lexer;
 use crate::quantity::{Quantity, Unit};
 
 use unicode_segmentation::UnicodeSegmentation;
@@ -8,6 +9,7 @@ use unicode_segmentation::UnicodeSegmentation;
 #[derive(Clone, Debug)]
 pub enum RValue {
     Void,
+    Bool(bool),
     Number(Quantity),
     String(String),
     Matrix(usize, usize, Vec<RValue>),
@@ -16,6 +18,7 @@ impl RValue {
     fn get_type(&self) -> &'static str {
         match &self {
             RValue::Void => "Void",
+            RValue::Bool(_) => "Bool",
             RValue::Number(_) => "Number",
             RValue::String(_) => "String",
             RValue::Matrix(_, _, _) => "Matrix", // (w,h,entries)
@@ -26,6 +29,7 @@ impl std::fmt::Display for RValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             RValue::Void => write!(f, "Void"),
+            RValue::Bool(b) => write!(f, "{b}"),
             RValue::Number(n) => write!(f, "{n}"),
             RValue::String(s) => write!(f, "{s}"),
             RValue::Matrix(w,h,v) => {
@@ -38,6 +42,7 @@ impl std::fmt::Display for RValue {
                             RValue::Number(_) => { format!("{}", v[j*w + i]) }
                             RValue::Matrix(_,_,_) => { format!("{}", v[j*w + i]) }
                             RValue::Void => { format!("{}", v[j*w + i]) }
+                            RValue::Bool(_) => { format!("{}", v[j*w + i]) }
                         };
                         str.push_str(&cell_str);
                         if i < w - 1 {
@@ -54,109 +59,680 @@ impl std::fmt::Display for RValue {
     }
 }
 
+impl From<Quantity> for RValue {
+    fn from(q: Quantity) -> RValue {
+        RValue::Number(q)
+    }
+}
+impl From<bool> for RValue {
+    fn from(b: bool) -> RValue {
+        RValue::Bool(b)
+    }
+}
+
+// error produced when running a whole program through `Evaluator::eval_str`
+#[derive(Clone, Debug)]
+pub struct EvalError {
+    pub message: String,
+}
+impl EvalError {
+    fn from_panic(payload: Box<dyn std::any::Any + Send>) -> EvalError {
+        // panic payloads are either a &str or a formatted String
+        let message = if let Some(s) = payload.downcast_ref::<&str>() {
+            s.to_string()
+        }else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        }else{
+            String::from("Unknown error")
+        };
+        EvalError { message }
+    }
+}
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for EvalError {}
+
 pub struct Evaluator {
     tree: Tree,
-    vars: HashMap<String, RValue>
+    vars: HashMap<String, RValue>,
+    // a 'while' loop running for more iterations is considered infinite
+    pub max_while_iterations: usize,
+    // expressions nested more deeply are an error, the stack of the evaluation is sized after it
+    pub max_depth: usize,
 }
 impl Evaluator {
     pub fn from_tree(tree: Tree) -> Self {
         Evaluator {
-            tree: tree, vars: HashMap::new()
+            tree, vars: HashMap::new(), max_while_iterations: DEFAULT_MAX_WHILE_ITERATIONS, max_depth: DEFAULT_MAX_DEPTH,
         }
     }
+    // the evaluation runs on a thread with room for max_depth nested expressions,
+    // its errors are raised again on the calling thread
     pub fn eval(&mut self) -> RValue {
-        self.tree.eval(&mut self.vars)
+        let max_while_iterations = self.max_while_iterations;
+        let max_depth = self.max_depth;
+        let tree = &self.tree;
+        let vars = &mut self.vars;
+        let evaluation = std::thread::scope(|scope| {
+            let mut builder = std::thread::Builder::new()
+                .stack_size(max_depth.saturating_mul(DEPTH_STACK_SIZE).saturating_add(EVAL_STACK_SIZE));
+            // with the name of the calling thread its panics are reported the same way
+            if let Some(name) = std::thread::current().name() {
+                builder = builder.name(String::from(name));
+            }
+            let evaluation = builder.spawn_scoped(scope, move || {
+                MAX_WHILE_ITERATIONS.with(|max| max.set(max_while_iterations));
+                MAX_DEPTH.with(|max| max.set(max_depth));
+                tree.eval(vars)
+            });
+            match evaluation {
+                Ok(handle) => handle.join(),
+                Err(error) => { panic!("Unable to start the evaluation: {error}"); }
+            }
+        });
+        match evaluation {
+            Ok(res) => res,
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+    // lexes, parses and evaluates the code returning the final value
+    // errors (which are raised as panics internally) are caught and returned
+    // the code is parsed on its own thread, with the stack of an ordinary main thread
+    // whatever the stack of the calling thread is, and whose panics are not printed to stderr
+    pub fn eval_str(code: &str) -> Result<RValue, EvalError> {
+        install_quiet_panic_hook();
+        let code = String::from(code);
+        let evaluation = std::thread::Builder::new()
+            .name(String::from(EVAL_STR_THREAD_NAME))
+            .stack_size(EVAL_STR_STACK_SIZE)
+            .spawn(move || {
+                let mut lexer = Lexer::new();
+                lexer.text = code;
+                lexer.lex();
+                let mut evaluator = Evaluator::from_tree(crate::ast::ast(&lexer.lexems));
+                evaluator.eval()
+            });
+        match evaluation {
+            Ok(handle) => handle.join().map_err(EvalError::from_panic),
+            Err(error) => Err(EvalError { message: format!("Unable to start the evaluation: {error}") }),
+        }
+    }
+    // forgets every variable, keeping the tree and the limits
+    pub fn reset(&mut self) {
+        self.vars.clear();
     }
     pub fn set_var(&mut self, varname: String, value: RValue) {
         self.vars.insert(varname, value);
     }
+    pub fn get_var(&self, varname: &str) -> Option<&RValue> {
+        self.vars.get(varname)
+    }
+    pub fn get_var_display(&self, varname: &str) -> Option<String> {
+        self.vars.get(varname).map(|rvalue| format!("{rvalue}"))
+    }
+    // the names of the defined variables in a stable, sorted, order
+    pub fn var_names(&self) -> Vec<String> {
+        variable_names(&self.vars)
+    }
 }
 
-macro_rules! eval_number_unary_operator { 
-    ($name:literal, $children:expr, $vars:expr, $n0:ident, $body:expr) => {
-        { 
-            if $children.len() == 1 {
-                let childval0: RValue = $children[0].eval($vars);
-                match childval0 {
-                    RValue::Number($n0) => {
-                        return RValue::Number($body);
-                    }
-                    _ => {
-                        panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found.", $name, childval0.get_type());
+// the hash map has no stable order, so the names are sorted
+fn variable_names(vars: &HashMap<String, RValue>) -> Vec<String> {
+    let mut names: Vec<String> = vars.keys().cloned().collect();
+    names.sort();
+    names
+}
+
+// the stack of the thread running Evaluator::eval_str
+const EVAL_STR_STACK_SIZE: usize = 8 * 1024 * 1024;
+const EVAL_STR_THREAD_NAME: &str = "tera_lang eval_str";
+
+// the errors of eval_str are returned, so the panics raising them are not reported:
+// the hook set before the first call keeps handling the panics of every other thread
+fn install_quiet_panic_hook() {
+    static INSTALLED: std::sync::Once = std::sync::Once::new();
+    INSTALLED.call_once(|| {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if std::thread::current().name() != Some(EVAL_STR_THREAD_NAME) {
+                previous_hook(info);
+            }
+        }));
+    });
+}
+
+// bitwise operations on non negative integers
+fn bitwise(name: &str, n0: &Quantity, n1: &Quantity) -> Quantity {
+    let mut ints = [0u64; 2];
+    for (k, n) in [n0, n1].iter().enumerate() {
+        if n.is_integer() && n.unit.is_unitless() && n.re >= 0.0 && n.re < 18446744073709551616.0 {
+            ints[k] = n.re as u64;
+        }else{
+            panic!("The '{name}' operator operates on pure, non negative, integer, unitless values but '{n}' was found.");
+        }
+    }
+    let [a, b] = ints;
+    let res = match name {
+        "band" => a & b,
+        "bor" => a | b,
+        "bxor" => a ^ b,
+        "shl" => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)).filter(|r| r >> b == a)
+            .unwrap_or_else(|| panic!("Overflow happened while shifting {a} to the left by {b}.")),
+        "shr" => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)).unwrap_or(0),
+        _ => { panic!("Unknown bitwise operator '{name}'"); }
+    };
+    (res as f64).into()
+}
+
+pub const DEFAULT_MAX_WHILE_ITERATIONS: usize = 10_000_000;
+
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+// stack used by each nested expression, the frames of unoptimized builds are far larger
+const DEPTH_STACK_SIZE: usize = if cfg!(debug_assertions) { 96 * 1024 } else { 8 * 1024 };
+// stack of the evaluation besides the nested expressions, parsing the blocks of strings for instance
+const EVAL_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+thread_local! {
+    // limits of the evaluator currently running, set by Evaluator::eval
+    static MAX_WHILE_ITERATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(DEFAULT_MAX_WHILE_ITERATIONS) };
+    static MAX_DEPTH: std::cell::Cell<usize> = const { std::cell::Cell::new(DEFAULT_MAX_DEPTH) };
+    // number of nested Tree::eval calls
+    static DEPTH: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// counts a nested evaluation for as long as it is alive, also while unwinding
+struct DepthGuard;
+impl DepthGuard {
+    fn enter() -> DepthGuard {
+        let depth = DEPTH.with(|d| { d.set(d.get() + 1); d.get() });
+        let guard = DepthGuard;
+        let max_depth = MAX_DEPTH.with(|max| max.get());
+        if depth > max_depth {
+            panic!("maximum recursion depth exceeded: expressions can be nested at most {max_depth} times");
+        }
+        guard
+    }
+}
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(d.get() - 1));
+    }
+}
+
+// reference instant of the 'now' function
+static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+
+// truth value of a condition, numbers are true when different from zero
+fn truthy(name: &str, value: &RValue) -> bool {
+    match value {
+        RValue::Bool(b) => *b,
+        RValue::Number(n) => *n != 0.0,
+        other => {
+            panic!("The '{name}' operator requires a condition of type 'Bool' or 'Number' but an element of type '{}' was found.", other.get_type());
+        }
+    }
+}
+
+// booleans are still the numbers 1 and 0 in arithmetic, as they were before having their own type
+fn numeric(value: RValue) -> RValue {
+    match value {
+        RValue::Bool(b) => RValue::Number(Quantity::real(if b { 1.0 } else { 0.0 })),
+        other => other,
+    }
+}
+
+// a cell is zero when both its real and imaginary parts are zero
+fn is_nonzero_cell(fname: &str, cell: &RValue) -> bool {
+    match cell {
+        RValue::Number(n) => n.re != 0.0 || n.im != 0.0,
+        RValue::Bool(b) => *b,
+        other => {
+            panic!("The '{fname}' function operates on cells of type 'Number' or 'Bool' but an element of type '{}' was found.", other.get_type());
+        }
+    }
+}
+
+// unit-aware equality between values of any type
+fn values_equal(a: &RValue, b: &RValue) -> bool {
+    match (a, b) {
+        (RValue::Void, RValue::Void) => true,
+        (RValue::Bool(b0), RValue::Bool(b1)) => b0 == b1,
+        (RValue::Number(n0), RValue::Number(n1)) => n0 == n1,
+        (RValue::String(s0), RValue::String(s1)) => s0 == s1,
+        (RValue::Matrix(w0, h0, v0), RValue::Matrix(w1, h1, v1)) => {
+            w0 == w1 && h0 == h1 && v0.iter().zip(v1.iter()).all(|(c0, c1)| values_equal(c0, c1))
+        }
+        _ => false,
+    }
+}
+
+// operators whose value only depends on the values of their two sides
+fn is_binary_operator(opname: &str) -> bool {
+    matches!(opname, "+" | "-" | "*" | "/" | "^" | "==" | ">" | ">=" | "<" | "<=" | "and" | "or" | "pm" | "band" | "bor" | "bxor" | "shl" | "shr")
+}
+
+// the two sides of an operator on numbers, label names the operator in the errors
+fn number_operands(label: &str, childval0: RValue, childval1: RValue) -> (Quantity, Quantity) {
+    match numeric(childval0) {
+        RValue::Number(n0) => {
+            match numeric(childval1) {
+                RValue::Number(n1) => (n0, n1),
+                other => {
+                    panic!("The {} operator operates on values of type 'Number' but an element of type '{}' was found on the right-hand side.", label, other.get_type());
+                }
+            }
+        }
+        other => {
+            panic!("The {} operator operates on values of type 'Number' but an element of type '{}' was found on the left-hand side.", label, other.get_type());
+        }
+    }
+}
+
+// the two sides of a comparison, which operates on real numbers
+fn real_operands(name: &str, childval0: RValue, childval1: RValue) -> (Quantity, Quantity) {
+    match numeric(childval0) {
+        RValue::Number(n0) => {
+            if !n0.is_real() {
+                panic!("The '{}' operator operates on values in the reals but on the left-hand side '{}' was found which has an imaginary part", name, n0);
+            }
+            match numeric(childval1) {
+                RValue::Number(n1) => {
+                    if !n1.is_real() {
+                        panic!("The '{}' operator operates on values in the reals but on the right-hand side '{}' was found which has an imaginary part", name, n1);
                     }
+                    (n0, n1)
                 }
+                other => {
+                    panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found on the right-hand side.", name, other.get_type());
+                }
+            }
+        }
+        other => {
+            panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found on the left-hand side.", name, other.get_type());
+        }
+    }
+}
+
+// applies a binary operator to the values of its two sides
+fn apply_binary_operator(opname: &str, childval0: RValue, childval1: RValue) -> RValue {
+    match opname {
+        "+" => {
+            let (n0, n1) = number_operands("binary '+'", childval0, childval1);
+            if n0.unit != n1.unit { panic!("The binary '+' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
+            RValue::Number(n0 + n1)
+        }
+        "-" => {
+            let (n0, n1) = number_operands("binary '-'", childval0, childval1);
+            if n0.unit != n1.unit { panic!("The binary '-' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
+            RValue::Number(n0 - n1)
+        }
+        "^" => {
+            let (n0, n1) = number_operands("'^'", childval0, childval1);
+            RValue::Number(n0.pow(&n1))
+        }
+        "*" => {
+            let (n0, n1) = number_operands("'*'", childval0, childval1);
+            RValue::Number(n0 * n1)
+        }
+        "/" => {
+            let (n0, n1) = number_operands("'/'", childval0, childval1);
+            RValue::Number(n0 / n1)
+        }
+        "==" => {
+            match (&childval0, &childval1) {
+                (RValue::Bool(_), RValue::Bool(_)) | (RValue::String(_), RValue::String(_)) => {
+                    RValue::Bool(values_equal(&childval0, &childval1))
+                }
+                // matrices are compared as a whole: same shape and equal cells, units included
+                // comparing them cell by cell is done with zipmap(a, b, "==")
+                (RValue::Matrix(..), RValue::Matrix(..)) => {
+                    RValue::Bool(values_equal(&childval0, &childval1))
+                }
+                // a boolean compared with a number counts as 1 or 0
+                _ => match (numeric(childval0), numeric(childval1)) {
+                    (RValue::Number(n0), RValue::Number(n1)) => {
+                        if n0.unit != n1.unit { panic!("The binary '==' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
+                        RValue::Bool(n0 == n1)
+                    }
+                    (v0, v1) => {
+                        panic!("The '==' operator cannot compare a value of type '{}' with one of type '{}'.", v0.get_type(), v1.get_type());
+                    }
+                },
+            }
+        }
+        ">" | ">=" | "<" | "<=" => {
+            let (n0, n1) = real_operands(opname, childval0, childval1);
+            if n0.unit != n1.unit { panic!("The binary '{}' operator operates on quantities with the same units but '{}' and '{}' were found.", opname, n0.unit, n1.unit) }
+            RValue::Bool(match opname {
+                ">" => n0.re > n1.re,
+                ">=" => n0.re >= n1.re,
+                "<" => n0.re < n1.re,
+                _ => n0.re <= n1.re,
+            })
+        }
+        "and" | "or" => {
+            let truth0 = truthy(opname, &childval0);
+            let truth1 = truthy(opname, &childval1);
+            RValue::Bool(if opname == "and" { truth0 && truth1 } else { truth0 || truth1 })
+        }
+        "band" | "bor" | "bxor" | "shl" | "shr" => {
+            let (n0, n1) = number_operands("'bitwise'", childval0, childval1);
+            RValue::Number(bitwise(opname, &n0, &n1))
+        }
+        "pm" => {
+            let (n0, n1) = number_operands("'pm'", childval0, childval1);
+            if n0.unit != n1.unit { panic!("The 'pm' operator operates only on quantities with the same units but '{}' and '{}' where found.", n0.unit, n1.unit); }
+            let mut res = n0;
+            res.vre = n1.re*n1.re;
+            res.vim = n1.im*n1.im;
+            RValue::Number(res)
+        }
+        _ => {
+            panic!("Unknown operator '{}'", opname);
+        }
+    }
+}
+
+// returns the cells of a row or column vector
+fn vector_cells(fname: &str, value: RValue) -> Vec<RValue> {
+    match value {
+        RValue::Matrix(w, h, v) => {
+            if w == 1 || h == 1 {
+                v
             }else{
-                panic!("The '{}' operator is unary only, but {} parameters were found.", $name, $children.len());
+                panic!("The '{fname}' function operates on vectors but a matrix {h}×{w} was found.");
             }
         }
-    } 
+        other => {
+            panic!("The '{fname}' function operates on vectors but an element of type '{}' was found.", other.get_type());
+        }
+    }
 }
 
-macro_rules! eval_number_binary_operator { 
-    ($name:literal, $children:expr, $vars:expr, $n0:ident, $n1:ident, $body:expr) => {
+// returns the cells of a matrix of numbers
+fn number_cells(fname: &str, value: RValue) -> Vec<Quantity> {
+    match value {
+        RValue::Matrix(_, _, v) => {
+            v.into_iter().map(|cell| match numeric(cell) {
+                RValue::Number(n) => n,
+                other => { panic!("The '{fname}' function operates on matrices of numbers but an element of type '{}' was found.", other.get_type()); }
+            }).collect()
+        }
+        other => {
+            panic!("The '{fname}' function operates on matrices but an element of type '{}' was found.", other.get_type());
+        }
+    }
+}
+
+// returns the width, the height and the cells of a matrix of numbers
+fn number_matrix(fname: &str, value: RValue) -> (usize, usize, Vec<Quantity>) {
+    let (w, h) = match &value {
+        RValue::Matrix(w, h, _) => (*w, *h),
+        other => { panic!("The '{fname}' function operates on matrices but an element of type '{}' was found.", other.get_type()); }
+    };
+    (w, h, number_cells(fname, value))
+}
+
+// returns the cells of a row or column vector of numbers
+fn vector_numbers(fname: &str, value: RValue) -> Vec<Quantity> {
+    let cells = vector_cells(fname, value);
+    number_cells(fname, RValue::Matrix(1, cells.len(), cells))
+}
+
+// numerical rank of a w×h row-major matrix via gaussian elimination with partial pivoting
+fn rank(w: usize, h: usize, mut a: Vec<f64>) -> usize {
+    let largest = a.iter().fold(0.0_f64, |m, x| m.max(x.abs()));
+    let tolerance = (w.max(h) as f64) * f64::EPSILON * largest;
+    let mut rank = 0;
+    for col in 0..w {
+        if rank == h { break; }
+        // the row with the largest pivot
+        let mut pivot = rank;
+        for row in rank..h {
+            if a[row*w + col].abs() > a[pivot*w + col].abs() { pivot = row; }
+        }
+        if a[pivot*w + col].abs() <= tolerance { continue; }
+        for k in 0..w { a.swap(rank*w + k, pivot*w + k); }
+        for row in (rank + 1)..h {
+            let factor = a[row*w + col] / a[rank*w + col];
+            for k in col..w { a[row*w + k] -= factor * a[rank*w + k]; }
+        }
+        rank += 1;
+    }
+    rank
+}
+
+// solves the n×n system a x = b via gaussian elimination with partial pivoting,
+// the arithmetic is done on quantities so units and uncertainties are propagated
+fn solve_linear(fname: &str, n: usize, mut a: Vec<Quantity>, mut b: Vec<Quantity>) -> Vec<Quantity> {
+    let magnitude = |q: &Quantity| q.re.hypot(q.im);
+    let largest = a.iter().fold(0.0_f64, |m, q| m.max(magnitude(q)));
+    let tolerance = (n as f64) * f64::EPSILON * largest;
+    for col in 0..n {
+        let mut pivot = col;
+        for row in col..n {
+            if magnitude(&a[row*n + col]) > magnitude(&a[pivot*n + col]) { pivot = row; }
+        }
+        if magnitude(&a[pivot*n + col]) <= tolerance {
+            panic!("The '{fname}' function cannot solve a system whose matrix is singular.");
+        }
+        for k in 0..n { a.swap(col*n + k, pivot*n + k); }
+        b.swap(col, pivot);
+        for row in (col + 1)..n {
+            let factor = a[row*n + col].clone() / a[col*n + col].clone();
+            for k in col..n {
+                a[row*n + k] = a[row*n + k].clone() - factor.clone() * a[col*n + k].clone();
+            }
+            b[row] = b[row].clone() - factor * b[col].clone();
+        }
+    }
+    // back substitution
+    let mut x: Vec<Quantity> = vec![0.0.into(); n];
+    for row in (0..n).rev() {
+        let mut acc = b[row].clone();
+        for k in (row + 1)..n {
+            acc = acc - a[row*n + k].clone() * x[k].clone();
+        }
+        x[row] = acc / a[row*n + row].clone();
+    }
+    x
+}
+
+// the real parts of a non empty vector of real same-unit numbers in increasing order
+fn sorted_reals(fname: &str, value: RValue) -> (Vec<f64>, Unit) {
+    let (mut reals, unit) = vector_reals(fname, value);
+    reals.sort_by(|a, b| a.total_cmp(b));
+    (reals, unit)
+}
+
+// returns the real values of a non empty vector of quantities sharing a unit
+fn vector_reals(fname: &str, value: RValue) -> (Vec<f64>, Unit) {
+    let cells = vector_numbers(fname, value);
+    if cells.is_empty() { panic!("The '{fname}' function operates on non empty vectors.") }
+    if let Some(n) = cells.iter().find(|n| !n.is_real()) {
+        panic!("The '{fname}' function operates on real quantities but '{n}' was found.");
+    }
+    check_same_unit(fname, &cells);
+    let unit = cells[0].unit.clone();
+    let reals: Vec<f64> = cells.into_iter().map(|n| n.re).collect();
+    (reals, unit)
+}
+
+// sample covariance of two vectors of the same length
+fn covariance(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+    x.iter().zip(y.iter()).map(|(a, b)| (a - mean_x) * (b - mean_y)).sum::<f64>() / (n - 1.0)
+}
+
+// panics if the quantities don't share the same unit
+fn check_same_unit(fname: &str, cells: &[Quantity]) {
+    if let Some(first) = cells.first() {
+        if let Some(other) = cells.iter().find(|n| n.unit != first.unit) {
+            panic!("The '{fname}' function operates on quantities with the same units but '{first}' and '{other}' were found.");
+        }
+    }
+}
+
+// interprets a value as a count of elements
+fn count_parameter(fname: &str, value: RValue) -> usize {
+    match value {
+        RValue::Number(n) => {
+            if n.is_integer() && n.unit.is_unitless() && n.re >= 0.0 {
+                n.re as usize
+            }else{
+                panic!("The '{fname}' function expects a pure, non negative, integer count but '{n}' was found.");
+            }
+        }
+        other => {
+            panic!("The '{fname}' function expects a count of type 'Number' but an element of type '{}' was found.", other.get_type());
+        }
+    }
+}
+
+// applies a built-in operator or function, given by name, to already evaluated values
+// the values are bound in a scratch scope so the variables of the program are never touched
+fn call_by_name(fname: &str, name: &str, args: Vec<RValue>) -> RValue {
+    let node = if is_binary_operator(name) {
+        if args.len() != 2 { panic!("The '{fname}' function can only apply the '{name}' operator to two values.") }
+        Node::Operator(String::from(name))
+    }else{
+        Node::FunctionCall(String::from(name))
+    };
+    let mut scope = HashMap::with_capacity(args.len());
+    let mut children = Vec::with_capacity(args.len());
+    for (k, arg) in args.into_iter().enumerate() {
+        let arg_name = format!("arg{k}");
+        scope.insert(arg_name.clone(), arg);
+        children.push(Tree { node: Node::Variable(arg_name), children: Vec::new(), has_value: true });
+    }
+    Tree { node, children, has_value: true }.eval(&mut scope)
+}
+
+// evaluates the parameters of range(start, end) or range(start, end, step)
+// returning the first value, the step and the number of values, end is excluded
+fn range_parameters(children: &[Tree], vars: &mut HashMap<String, RValue>) -> (Quantity, Quantity, usize) {
+    if children.len() != 2 && children.len() != 3 {
+        panic!("The 'range' function takes 2 or 3 parameters, but {} parameters were found.", children.len());
+    }
+    let mut params = Vec::with_capacity(children.len());
+    for child in children.iter() {
+        match child.eval(vars) {
+            RValue::Number(n) => {
+                if !n.is_real() || n.vre != 0.0 { panic!("The 'range' function operates on real quantities without uncertainty but '{n}' was found.") }
+                params.push(n);
+            }
+            other => { panic!("The 'range' function operates on numbers but an element of type '{}' was found.", other.get_type()); }
+        }
+    }
+    let start = params[0].clone();
+    let step = if params.len() == 3 { params[2].clone() } else { Quantity::with_unit(1.0, start.unit.clone()) };
+    check_same_unit("range", &params);
+    if step.re == 0.0 { panic!("The 'range' function needs a non zero step.") }
+    let count = ((params[1].re - start.re) / step.re).ceil().max(0.0);
+    // 'as' would saturate, so counts which are not representable are rejected
+    if count.is_nan() || count >= usize::MAX as f64 {
+        panic!("The 'range' function cannot produce {count} values, from {} to {} with a step of {}.", start, params[1], step);
+    }
+    (start, step, count as usize)
+}
+
+// interprets a value as a string parameter
+fn string_parameter(fname: &str, value: RValue) -> String {
+    match value {
+        RValue::String(s) => s,
+        other => {
+            panic!("The '{fname}' function expects a value of type 'String' but an element of type '{}' was found.", other.get_type());
+        }
+    }
+}
+
+// splits the content of a string block, '{x + 1|m| |cm|:.2}', into the expression, the unit
+// to display it with and the format specifier; a final unit right after a number, '{x + 1|m|}',
+// belongs to the number
+fn split_string_block(content: &[&str], str: &str) -> (String, String, Option<String>) {
+    let mut in_unit = false;
+    let mut colon = None;
+    for (k, c) in content.iter().enumerate() {
+        if *c == "|" {
+            in_unit = !in_unit;
+        }else if *c == ":" && !in_unit {
+            colon = Some(k);
+            break;
+        }
+    }
+    if in_unit {
+        panic!("Opening '|' inside string is missing a corresponding '|': '{str}'");
+    }
+    let format_spec = colon.map(|k| content[k + 1..].join(""));
+    let body = content[..colon.unwrap_or(content.len())].join("");
+    let body = body.trim();
+    if body.is_empty() {
+        panic!("String block contains an empty '{{}}': '{str}'");
+    }
+    if let Some(without_bar) = body.strip_suffix('|') {
+        // the bars are paired, so the unit has an opening one
+        let opening = without_bar.rfind('|').unwrap();
+        let before = without_bar[..opening].trim_end();
+        let last_word: String = before.chars().rev().take_while(|c| c.is_alphanumeric() || *c == '.' || *c == '_').collect();
+        let after_number = last_word.chars().last().is_some_and(|c| c.is_ascii_digit());
+        if !after_number {
+            if before.is_empty() {
+                panic!("String block contains an empty '{{}}': '{str}'");
+            }
+            return (before.to_owned(), without_bar[opening + 1..].trim().to_owned(), format_spec);
+        }
+    }
+    (body.to_owned(), String::new(), format_spec)
+}
+
+macro_rules! eval_number_unary_operator { 
+    ($name:literal, $children:expr, $vars:expr, $n0:ident, $body:expr) => {
         { 
-            if $children.len() == 2 {
-                let childval0: RValue = $children[0].eval($vars);
-                let childval1: RValue = $children[1].eval($vars);
+            if $children.len() == 1 {
+                let childval0: RValue = numeric($children[0].eval($vars));
                 match childval0 {
                     RValue::Number($n0) => {
-                        match childval1 {
-                            RValue::Number($n1) => {
-                                return RValue::Number($body);
-                            }
-                            _ => {
-                                panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found on the right-hand side.", $name, childval1.get_type());
-                            }
-                        }
+                        return RValue::from($body);
                     }
                     _ => {
-                        panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found on the left-hand side.", $name, childval0.get_type());
+                        panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found.", $name, childval0.get_type());
                     }
                 }
             }else{
-                panic!("The '{}' operator is binary only but a number of {} children were found.", $name, $children.len());
+                panic!("The '{}' operator is unary only, but {} parameters were found.", $name, $children.len());
             }
         }
     } 
 }
 
-macro_rules! eval_real_binary_operator { 
-    ($name:literal, $children:expr, $vars:expr, $n0:ident, $n1:ident, $body:expr) => {
+macro_rules! eval_number_unary_function { 
+    ($name:literal, $children:expr, $vars:expr, $n0:ident, $body:expr) => {
         { 
-            if $children.len() == 2 {
-                let childval0: RValue = $children[0].eval($vars);
-                let childval1: RValue = $children[1].eval($vars);
+            if $children.len() == 1 {
+                let childval0: RValue = numeric($children[0].eval($vars));
                 match childval0 {
                     RValue::Number($n0) => {
-                        if $n0.is_real() {
-                            match childval1 {
-                                RValue::Number($n1) => {
-                                    if $n1.is_real() {
-                                        return RValue::Number($body);
-                                    }else{
-                                        panic!("The '{}' operator operates on values in the reals but on the right-hand side '{}' was found which has an imaginary part", $name, $n1);
-                                    }
-                                }
-                                _ => {
-                                    panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found on the right-hand side.", $name, childval1.get_type());
-                                }
-                            }
-                        }else{
-                            panic!("The '{}' operator operates on values in the reals but on the left-hand side '{}' was found which has an imaginary part", $name, $n0);
-                        }
+                        return RValue::from($body);
                     }
                     _ => {
-                        panic!("The '{}' operator operates on values of type 'Number' but an element of type '{}' was found on the left-hand side.", $name, childval0.get_type());
+                        panic!("The '{}' function takes on value of type 'Number' but an element of type '{}' was found.", $name, childval0.get_type());
                     }
                 }
             }else{
-                panic!("The '{}' operator is binary only but a number of {} children were found.", $name, $children.len());
+                panic!("The '{}' function takes one parameter, but {} parameters were found.", $name, $children.len());
             }
         }
     } 
 }
 
-macro_rules! eval_number_unary_function { 
+// like eval_number_unary_function but matrices are mapped cell by cell
+macro_rules! eval_number_elementwise_function { 
     ($name:literal, $children:expr, $vars:expr, $n0:ident, $body:expr) => {
         { 
             if $children.len() == 1 {
@@ -165,8 +741,20 @@ macro_rules! eval_number_unary_function {
                     RValue::Number($n0) => {
                         return RValue::Number($body);
                     }
+                    RValue::Matrix(w, h, v) => {
+                        let mut res = Vec::with_capacity(v.len());
+                        for cell in v.into_iter() {
+                            match cell {
+                                RValue::Number($n0) => { res.push(RValue::Number($body)); }
+                                other => {
+                                    panic!("The '{}' function operates on matrices of numbers but an element of type '{}' was found.", $name, other.get_type());
+                                }
+                            }
+                        }
+                        return RValue::Matrix(w, h, res);
+                    }
                     _ => {
-                        panic!("The '{}' function takes on value of type 'Number' but an element of type '{}' was found.", $name, childval0.get_type());
+                        panic!("The '{}' function takes on value of type 'Number' or 'Matrix' but an element of type '{}' was found.", $name, childval0.get_type());
                     }
                 }
             }else{
@@ -180,13 +768,13 @@ macro_rules! eval_number_binary_function {
     ($name:literal, $children:expr, $vars:expr, $n0:ident, $n1:ident, $body:expr) => {
         { 
             if $children.len() == 2 {
-                let childval0: RValue = $children[0].eval($vars);
-                let childval1: RValue = $children[1].eval($vars);
+                let childval0: RValue = numeric($children[0].eval($vars));
+                let childval1: RValue = numeric($children[1].eval($vars));
                 match childval0 {
                     RValue::Number($n0) => {
                         match childval1 {
                             RValue::Number($n1) => {
-                                return RValue::Number($body);
+                                return RValue::from($body);
                             }
                             _ => {
                                 panic!("The '{}' function takes two values of type 'Number' but an element of type '{}' was found as second parameter.", $name, childval1.get_type());
@@ -204,21 +792,74 @@ macro_rules! eval_number_binary_function {
     } 
 }
 
+macro_rules! eval_number_ternary_function { 
+    ($name:literal, $children:expr, $vars:expr, $n0:ident, $n1:ident, $n2:ident, $body:expr) => {
+        { 
+            if $children.len() == 3 {
+                let childval0: RValue = numeric($children[0].eval($vars));
+                let childval1: RValue = numeric($children[1].eval($vars));
+                let childval2: RValue = numeric($children[2].eval($vars));
+                match (childval0, childval1, childval2) {
+                    (RValue::Number($n0), RValue::Number($n1), RValue::Number($n2)) => {
+                        return RValue::from($body);
+                    }
+                    (v0, v1, v2) => {
+                        panic!("The '{}' function takes three values of type 'Number' but elements of type '{}', '{}' and '{}' were found.", $name, v0.get_type(), v1.get_type(), v2.get_type());
+                    }
+                }
+            }else{
+                panic!("The '{}' function takes three parameters, but {} parameters were found.", $name, $children.len());
+            }
+        }
+    } 
+}
+
 impl Tree {
+    // evaluates a binary operator, the left-deep chains the parser builds for 1 + 2 - 3 + ...
+    // are walked down without recursion
+    fn eval_binary_chain(&self, vars: &mut HashMap<String, RValue>) -> RValue {
+        let mut chain = vec![self];
+        let mut leftmost = &self.children[0];
+        while let Node::Operator(opname) = &leftmost.node {
+            if leftmost.children.len() != 2 || !is_binary_operator(opname) {
+                break;
+            }
+            chain.push(leftmost);
+            leftmost = &leftmost.children[0];
+        }
+        let mut res = leftmost.eval(vars);
+        for tree in chain.iter().rev() {
+            if let Node::Operator(opname) = &tree.node {
+                let childval1 = tree.children[1].eval(vars);
+                res = apply_binary_operator(opname, res, childval1);
+            }
+        }
+        res
+    }
+
     fn eval(&self, vars: &mut HashMap<String, RValue>) -> RValue {
+        let _depth = DepthGuard::enter();
         match &self.node {
             Node::Number(val, dec) => {
+                // 3alpha is 3*alpha when 'alpha' is a variable and not a unit, while 3m stays 3 metres
+                if !dec.is_empty() && dec != "i" && dec != "j" && Unit::try_parse_single_unit(dec).is_err() {
+                    if let Some(factor) = vars.get(dec).cloned() {
+                        return apply_binary_operator("*", RValue::Number(Quantity::from_value_decorator(*val, &String::new())), factor);
+                    }
+                }
                 // TODO: number to value
                 RValue::Number(Quantity::from_value_decorator(*val, dec))
             }
             Node::Operator(opname) => {
                 let length = self.children.len();
                 match &opname[..] {
-                    "!" => {
-                        eval_number_unary_operator!("!", self.children, vars, n0, if n0 == 0.0 {1.0.into()} else {0.0.into()})
-                    }
-                    "?" => {
-                        eval_number_unary_operator!("?", self.children, vars, n0, if n0 != 0.0 {1.0.into()} else {0.0.into()})
+                    "!" | "?" => {
+                        if length == 1 {
+                            let truth = truthy(opname, &self.children[0].eval(vars));
+                            RValue::Bool(if opname == "!" { !truth } else { truth })
+                        }else{
+                            panic!("The '{}' operator is unary only, but {} parameters were found.", opname, length);
+                        }
                     }
                     "&" => {
                         eval_number_unary_operator!("&", self.children, vars, n0, n0.sigma())
@@ -226,127 +867,78 @@ impl Tree {
                     "$" => {
                         eval_number_unary_operator!("$", self.children, vars, n0, n0.value())
                     }
+                    // a chain such as 1 + 2 - 3 + ... is not nested, its length does not count as depth
+                    _ if length == 2 && is_binary_operator(opname) => {
+                        self.eval_binary_chain(vars)
+                    }
                     "+" => {
                         if length == 1 {
-                            let childval = self.children[0].eval(vars);
+                            let childval = numeric(self.children[0].eval(vars));
                             match childval {
                                 RValue::Number(_) => {
-                                    return childval;
+                                    childval
                                 }
                                 _ => {
                                     panic!("The unary '+' operator operates on values of type 'Number' but an element of type '{}' was found.", childval.get_type());
                                 }
                             }
-                        }else if length == 2 {
-                            let childval0 = self.children[0].eval(vars);
-                            let childval1 = self.children[1].eval(vars);
-                            match childval0 {
-                                RValue::Number(n0) => {
-                                    match childval1 {
-                                        RValue::Number(n1) => {
-                                            if n0.unit != n1.unit { panic!("The binary '+' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
-                                            return RValue::Number(n0 + n1);
-                                        }
-                                        _ => {
-                                            panic!("The binary '+' operator operates on values of type 'Number' but an element of type '{}' was found on the right-hand side.", childval1.get_type());
-                                        }
-                                    }
-                                }
-                                _ => {
-                                    panic!("The binary '+' operator operates on values of type 'Number' but an element of type '{}' was found on the left-hand side.", childval0.get_type());
-                                }
-                            }
                         }else{
                             panic!("The '+' operator can be either unary or binary but a number of {} children were found.", length)
                         }
                     }
                     "-" => {
                         if length == 1 {
-                            let childval = self.children[0].eval(vars);
+                            let childval = numeric(self.children[0].eval(vars));
                             match childval {
                                 RValue::Number(n) => {
-                                    return RValue::Number(-n);
+                                    RValue::Number(-n)
                                 }
-                                _ => {
-                                    panic!("The unary '-' operator operates on values of type 'Number' but an element of type '{}' was found.", childval.get_type());
-                                }
-                            }
-                        }else if length == 2 {
-                            let childval0 = self.children[0].eval(vars);
-                            let childval1 = self.children[1].eval(vars);
-                            match childval0 {
-                                RValue::Number(n0) => {
-                                    match childval1 {
-                                        RValue::Number(n1) => {
-                                            if n0.unit != n1.unit { panic!("The binary '-' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
-                                            return RValue::Number(n0 - n1);
-                                        }
-                                        _ => {
-                                            panic!("The binary '-' operator operates on values of type 'Number' but an element of type '{}' was found on the right-hand side.", childval1.get_type());
-                                        }
-                                    }
+                                RValue::Matrix(w, h, v) => {
+                                    // every cell is negated
+                                    let res = v.into_iter().map(|cell| match cell {
+                                        RValue::Number(n) => RValue::Number(-n),
+                                        other => { panic!("The unary '-' operator operates on matrices of numbers but an element of type '{}' was found.", other.get_type()); }
+                                    }).collect();
+                                    RValue::Matrix(w, h, res)
                                 }
                                 _ => {
-                                    panic!("The binary '-' operator operates on values of type 'Number' but an element of type '{}' was found on the left-hand side.", childval0.get_type());
+                                    panic!("The unary '-' operator operates on values of type 'Number' or 'Matrix' but an element of type '{}' was found.", childval.get_type());
                                 }
                             }
                         }else{
                             panic!("The '-' operator can be either unary or binary but a number of {} children were found.", length)
                         }
                     }
-                    "^" => {
-                        todo!() // eval_number_binary_operator!("^", self.children, vars, n0, n1, n0.powf(n1)) 
-                    }
-                    "*" => {
-                        eval_number_binary_operator!("*", self.children, vars, n0, n1, n0 * n1)
-                    }
-                    "/" => {
-                        eval_number_binary_operator!("/", self.children, vars, n0, n1, n0 / n1)
-                    }
-                    "==" => {
-                        eval_number_binary_operator!("==", self.children, vars, n0, n1, {
-                            if n0.unit != n1.unit { panic!("The binary '==' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
-                            if n0 == n1 { 1.0.into() } else { 0.0.into() }
-                        } )
-                    }
-                    ">" => {
-                        eval_real_binary_operator!(">", self.children, vars, n0, n1, {
-                            if n0.unit != n1.unit { panic!("The binary '>' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
-                            if n0.re > n1.re { 1.0.into() } else { 0.0.into() }
-                        } )
-                    }
-                    ">=" => {
-                        eval_real_binary_operator!(">=", self.children, vars, n0, n1, {
-                            if n0.unit != n1.unit { panic!("The binary '>=' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
-                            if n0.re >= n1.re { 1.0.into() } else { 0.0.into() }
-                        } )
-                    }
-                    "<" => {
-                        eval_real_binary_operator!("<", self.children, vars, n0, n1, {
-                            if n0.unit != n1.unit { panic!("The binary '<' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
-                            if n0.re < n1.re { 1.0.into() } else { 0.0.into() }
-                        } )
-                    }
-                    "<=" => {
-                        eval_real_binary_operator!("<=", self.children, vars, n0, n1, {
-                            if n0.unit != n1.unit { panic!("The binary '<=' operator operates on quantities with the same units but '{}' and '{}' were found.", n0.unit, n1.unit) }
-                            if n0.re <= n1.re { 1.0.into() } else { 0.0.into() }
-                        } )
-                    }
-                    "and" => {
-                        eval_number_binary_operator!("and", self.children, vars, n0, n1, if n0 != 0.0 && n1 != 0.0 {1.0.into()} else {0.0.into()} )
-                    }
-                    "or" => {
-                        eval_number_binary_operator!("or", self.children, vars, n0, n1, if n0 != 0.0 || n1 != 0.0 {1.0.into()} else {0.0.into()} )
-                    }
                     "=" => {
                         if self.children.len() == 2 {
                             let child0: &Node = &self.children[0].node;
                             if let Node::Variable(varname) = child0 {
                                 // TODO: what if they create a variable with the same name of a function?
+                                // the assignment has the assigned value so that a = b = 5 works
                                 let childvar1 = self.children[1].eval(vars);
-                                vars.insert(varname.clone(), childvar1);
-                                RValue::Void
+                                vars.insert(varname.clone(), childvar1.clone());
+                                childvar1
+                            }else if let Node::MatrixBlock(_, _) = child0 {
+                                // destructuring: (a, b) = (1, 2)
+                                let mut varnames = Vec::with_capacity(self.children[0].children.len());
+                                for target in self.children[0].children.iter() {
+                                    match &target.node {
+                                        Node::Variable(varname) => { varnames.push(varname.clone()); }
+                                        _ => { panic!("The '=' operator can only destructure into variable names but {:?} was found.", target); }
+                                    }
+                                }
+                                let childvar1 = self.children[1].eval(vars);
+                                let values = match &childvar1 {
+                                    RValue::Matrix(w, h, v) if *w == 1 || *h == 1 => v.clone(),
+                                    other => { panic!("Only vectors can be destructured but '{}' was found.", other); }
+                                };
+                                if values.len() != varnames.len() {
+                                    panic!("Cannot destructure {} values into {} variables.", values.len(), varnames.len());
+                                }
+                                for (varname, value) in varnames.into_iter().zip(values) {
+                                    vars.insert(varname, value);
+                                }
+                                childvar1
                             }else{
                                 panic!("The '=' operator expects a variable name on the left-hand side.");
                             }
@@ -355,52 +947,33 @@ impl Tree {
                         }
                     }
                     "if" => {
-                        if self.children.len() == 2 {
-                            // IF 
-                            if let RValue::Number(condition) = &self.children[0].eval(vars) {
-                                if *condition != 0.0 {
-                                    self.children[1].eval(vars)
-                                }else{
-                                    RValue::Void
-                                }
+                        if self.children.len() == 2 || self.children.len() == 3 {
+                            // IF, IF ELSE
+                            // the value of the statement is the value of the chosen branch
+                            // so that it can be used as an expression: x = if c { a } else { b }
+                            if truthy("if", &self.children[0].eval(vars)) {
+                                self.children[1].eval(vars)
+                            }else if self.children.len() == 3 {
+                                self.children[2].eval(vars)
                             }else{
                                 RValue::Void
                             }
-                        }else if self.children.len() == 3 {
-                            // IF ELSE
-                            if let RValue::Number(condition) = &self.children[0].eval(vars) {
-                                if *condition != 0.0 {
-                                    self.children[1].eval(vars)
-                                }else{
-                                    self.children[2].eval(vars)
-                                }
-                            }else{
-                                self.children[2].eval(vars)
-                            }
                         }else{
                             panic!("The 'if' operator is a prefixed binary or ternary operator but a number of {} children were found.", self.children.len());
                         }
                     }
-                    "pm" => {
-                        eval_number_binary_operator!("pm", self.children, vars, n0, n1, { 
-                            if n0.unit != n1.unit { panic!("The 'pm' operator operates only on quantities with the same units but '{}' and '{}' where found.", n0.unit, n1.unit); }
-                            let mut res = n0.clone();
-                            res.vre = n1.re*n1.re;
-                            res.vim = n1.im*n1.im;
-                            res    
-                        } )
+                    _ if is_binary_operator(opname) => {
+                        panic!("The '{}' operator is binary only but a number of {} children were found.", opname, length);
                     }
                     "while" => {
                         if self.children.len() == 2 {
                             // WHILE 
                             let mut res: Vec<RValue> = Vec::new();
-                            while {
-                                let ev = &self.children[0].eval(vars);
-                                let condition = if let RValue::Number(cond) = ev { cond } else {
-                                    panic!("While statements require numeric values as condition but {} was found.", ev);
-                                };
-                                *condition != 0.0
-                            } {
+                            let max_iterations = MAX_WHILE_ITERATIONS.with(|max| max.get());
+                            while truthy("while", &self.children[0].eval(vars)) {
+                                if res.len() == max_iterations {
+                                    panic!("while loop exceeded {max_iterations} iterations, possible infinite loop");
+                                }
                                 res.push(self.children[1].eval(vars));                                
                             }
                             RValue::Matrix(1, res.len(), res)
@@ -409,10 +982,18 @@ impl Tree {
                         }
                     }
                     "for" => {
-                        if self.children.len() == 3 {
-                            // FOR
-                            if let Node::Variable(index_name) = &self.children[0].node {
-                                if let Node::Variable(matrix_name) = &self.children[1].node {
+                        if self.children.len() == 3 || self.children.len() == 4 {
+                            // FOR, in 'for i, x in matrix' the first child is the position of x
+                            let (position_name, children) = if self.children.len() == 4 {
+                                match &self.children[0].node {
+                                    Node::Variable(name) => (Some(name), &self.children[1..]),
+                                    _ => { panic!("The element after a 'for' operator must be a valid variable name. Found {:?} instead, which is not a variable name.", self.children[0]); }
+                                }
+                            }else{
+                                (None, &self.children[..])
+                            };
+                            if let Node::Variable(index_name) = &children[0].node {
+                                if let Node::Variable(matrix_name) = &children[1].node {
                                     // if we iterate on a variable we avoid evaluating the expression and
                                     // use the variable directly
                                     let matrix: &RValue = match vars.get(matrix_name) { 
@@ -424,9 +1005,10 @@ impl Tree {
                                         _ => { panic!("'{}' is not a variable containing a matrix.", matrix_name) } 
                                     };
                                     // actually executing the for statement
+                                    // the cells are visited row by row, the order in which they are stored and numbered
                                     let mut res_vec = Vec::with_capacity(w*h);
-                                    for x in 0..w {
-                                        for y in 0..h {
+                                    for y in 0..h {
+                                        for x in 0..w {
                                             let matrix: &RValue = match vars.get(matrix_name) { 
                                                 Some(m) => m, 
                                                 None => { panic!("'{}' is not an existing variable.", matrix_name) }
@@ -435,34 +1017,56 @@ impl Tree {
                                                 RValue::Matrix(_, _, v) => { (v[y*w + x]).clone() },
                                                 _ => { panic!("'{}' is not a variable containing a matrix.", matrix_name) } 
                                             };
+                                            if let Some(position_name) = position_name {
+                                                vars.insert(position_name.clone(), RValue::Number(Quantity::real((y*w + x + 1) as f64)));
+                                            }
                                             vars.insert(index_name.clone(), cur);
-                                            res_vec.push(self.children[2].eval(vars));
+                                            res_vec.push(children[2].eval(vars));
                                         }
                                     }
                                     RValue::Matrix(w, h, res_vec)
-                                }else if self.children[1].has_value {
-                                    let matrix: RValue = self.children[1].eval(vars);
+                                }else if matches!(&children[1].node, Node::FunctionCall(fname) if fname == "range") {
+                                    // for x in range(a, b) produces the values one at a time
+                                    // without building the matrix being iterated on
+                                    // the results are collected as the loop runs, a long range costs nothing upfront
+                                    let (start, step, count) = range_parameters(&children[1].children, vars);
+                                    let mut res_vec = Vec::new();
+                                    for k in 0..count {
+                                        let cur = Quantity::with_unit(start.re + (k as f64)*step.re, start.unit.clone());
+                                        if let Some(position_name) = position_name {
+                                            vars.insert(position_name.clone(), RValue::Number(Quantity::real((k + 1) as f64)));
+                                        }
+                                        vars.insert(index_name.clone(), RValue::Number(cur));
+                                        res_vec.push(children[2].eval(vars));
+                                    }
+                                    RValue::Matrix(1, count, res_vec)
+                                }else if children[1].has_value {
+                                    let matrix: RValue = children[1].eval(vars);
                                     let (w, h, vec_matrix) = match matrix {
                                         RValue::Matrix(w, h, vec_matrix) => (w, h, vec_matrix),
                                         value => { panic!("'for' statements iterate over matrices but the given expression was evaluated as {}, which is not a matrix.", value) } 
                                     };
                                     // actually executing the for statement
+                                    // the cells are visited row by row, the order in which they are stored and numbered
                                     let mut res_vec = Vec::with_capacity(w*h);
-                                    for x in 0..w {
-                                        for y in 0..h {
+                                    for y in 0..h {
+                                        for x in 0..w {
+                                            if let Some(position_name) = position_name {
+                                                vars.insert(position_name.clone(), RValue::Number(Quantity::real((y*w + x + 1) as f64)));
+                                            }
                                             vars.insert(index_name.clone(), vec_matrix[y*w + x].clone());
-                                            res_vec.push(self.children[2].eval(vars));
+                                            res_vec.push(children[2].eval(vars));
                                         }
                                     }
                                     RValue::Matrix(w, h, res_vec)
                                 }else{
-                                    panic!("The element after the 'in' keyword of a 'for' statement must be a valid variable name or a valued expression. Found {:?} instead.", self.children[1]);
+                                    panic!("The element after the 'in' keyword of a 'for' statement must be a valid variable name or a valued expression. Found {:?} instead.", children[1]);
                                 }
                             }else{
-                                panic!("The element after a 'for' operator must be a valid variable name. Found {:?} instead, which is not a variable name.", self.children[0]);
+                                panic!("The element after a 'for' operator must be a valid variable name. Found {:?} instead, which is not a variable name.", children[0]);
                             }
                         }else{
-                            panic!("The 'for' operator should have three children but a number of {} children was found.", self.children.len());
+                            panic!("The 'for' operator should have three or four children but a number of {} children was found.", self.children.len());
                         }
                     }
                     _ => {
@@ -474,34 +1078,50 @@ impl Tree {
                 match &fname[..] {
                     // ONE PARAMETER FUNCTIONS
                     "sin" => {
-                        eval_number_unary_function!("sin", self.children, vars, n, {
+                        eval_number_elementwise_function!("sin", self.children, vars, n, {
                             if !n.unit.is_unitless() { panic!("The 'sin' function operates on unitless quantities but '{n}' was found.") }
                             n.sin()
                         })
                     }
                     "cos" => {
-                        eval_number_unary_function!("cos", self.children, vars, n, {
+                        eval_number_elementwise_function!("cos", self.children, vars, n, {
                             if !n.unit.is_unitless() { panic!("The 'cos' function operates on unitless quantities but '{n}' was found.") }
                             n.cos()
                         })
                     }
                     "i" => {
                         // multiply by the imaginary unit
-                        eval_number_unary_function!("i", self.children, vars, n, Quantity {
+                        eval_number_elementwise_function!("i", self.children, vars, n, Quantity {
                             re: -n.im, im: n.re, vre: n.vim, vim: n.vre, unit: n.unit
                         })
                     }
                     "exp" => {
-                        eval_number_unary_function!("exp", self.children, vars, n, {
+                        eval_number_elementwise_function!("exp", self.children, vars, n, {
                             if !n.unit.is_unitless() { panic!("The 'exp' function operates on unitless quantities but '{n}' was found.") }
                             n.exp()
                         })
                     }
+                    "sqrt" => {
+                        eval_number_elementwise_function!("sqrt", self.children, vars, n, n.nthroot(2))
+                    }
+                    "ln" => {
+                        eval_number_elementwise_function!("ln", self.children, vars, n, {
+                            if !n.unit.is_unitless() { panic!("The 'ln' function operates on unitless quantities but '{n}' was found.") }
+                            n.ln()
+                        })
+                    }
                     "Re" | "real" => {
-                        eval_number_unary_function!("Re", self.children, vars, n, n.real_part())
+                        eval_number_elementwise_function!("Re", self.children, vars, n, n.real_part())
                     }
                     "Im" | "imag" => {
-                        eval_number_unary_function!("Im", self.children, vars, n, n.imag_part())
+                        eval_number_elementwise_function!("Im", self.children, vars, n, n.imag_part())
+                    }
+                    "type" => {
+                        if self.children.len() == 1 {
+                            RValue::String(String::from(self.children[0].eval(vars).get_type()))
+                        }else{
+                            panic!("The 'type' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
                     }
                     "sigma" => {
                         eval_number_unary_function!("sigma", self.children, vars, n, n.sigma())
@@ -513,12 +1133,173 @@ impl Tree {
                         eval_number_unary_function!("value", self.children, vars, n, n.value())
                     }
                     "abs" => {
-                        eval_number_unary_function!("value", self.children, vars, n, n.abs())
+                        eval_number_elementwise_function!("abs", self.children, vars, n, n.abs())
                     }
                     "arg" => {
-                        eval_number_unary_function!("value", self.children, vars, n, n.arg())
+                        eval_number_elementwise_function!("arg", self.children, vars, n, n.arg())
+                    }
+                    "arg_positive" => {
+                        eval_number_elementwise_function!("arg_positive", self.children, vars, n, n.arg_positive())
+                    }
+                    "to_si" => {
+                        // quantities are always stored in SI units
+                        eval_number_unary_function!("to_si", self.children, vars, n, n)
+                    }
+                    "value_si" => {
+                        eval_number_unary_function!("value_si", self.children, vars, n, Quantity { unit: Unit::unitless(), ..n })
+                    }
+                    "dimensionless" => {
+                        eval_number_unary_function!("dimensionless", self.children, vars, n, n.unit.is_unitless())
+                    }
+                    "isnan" => {
+                        eval_number_unary_function!("isnan", self.children, vars, n, n.re.is_nan() || n.im.is_nan())
+                    }
+                    "isinf" => {
+                        eval_number_unary_function!("isinf", self.children, vars, n, n.re.is_infinite() || n.im.is_infinite())
+                    }
+                    "clamp01" => {
+                        eval_number_unary_function!("clamp01", self.children, vars, n, {
+                            if !n.unit.is_unitless() { panic!("The 'clamp01' function operates on unitless quantities but '{n}' was found.") }
+                            if !n.is_real() { panic!("The 'clamp01' function operates on real quantities but '{n}' was found.") }
+                            if n.re < 0.0 { 0.0.into() } else if n.re > 1.0 { 1.0.into() } else { n }
+                        })
                     }
                     // TWO PARAMETERS FUNCTIONS
+                    "nthroot" => {
+                        if self.children.len() == 2 {
+                            let childval0 = self.children[0].eval(vars);
+                            // the index divides the unit exponents, which are i8
+                            let index = match self.children[1].eval(vars) {
+                                RValue::Number(k) if k.is_real() && k.vre == 0.0 && k.unit.is_unitless() && k.re == k.re.floor() && k.re >= 1.0 && k.re <= i8::MAX as f64 => k.re as u32,
+                                RValue::Number(k) => {
+                                    panic!("The 'nthroot' function takes a pure integer index between 1 and {} but '{k}' was found.", i8::MAX);
+                                }
+                                other => {
+                                    panic!("The 'nthroot' function takes an index of type 'Number' but an element of type '{}' was found.", other.get_type());
+                                }
+                            };
+                            match childval0 {
+                                RValue::Number(n) => RValue::Number(n.nthroot(index)),
+                                other => {
+                                    panic!("The 'nthroot' function takes a value of type 'Number' as first parameter but an element of type '{}' was found.", other.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'nthroot' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "band" | "bor" | "bxor" | "shl" | "shr" => {
+                        eval_number_binary_function!("bitwise", self.children, vars, n0, n1, bitwise(fname, &n0, &n1))
+                    }
+                    "range" => {
+                        // column vector of the values from start (included) to end (excluded)
+                        let (start, step, count) = range_parameters(&self.children, vars);
+                        let mut res = Vec::new();
+                        if res.try_reserve_exact(count).is_err() {
+                            panic!("The 'range' function cannot hold {count} values in memory, 'for x in range(..)' loops over them without storing them.");
+                        }
+                        for k in 0..count {
+                            res.push(RValue::Number(Quantity::with_unit(start.re + (k as f64)*step.re, start.unit.clone())));
+                        }
+                        RValue::Matrix(1, count, res)
+                    }
+                    "floor" => {
+                        eval_number_elementwise_function!("floor", self.children, vars, n, n.floor())
+                    }
+                    "ceil" => {
+                        eval_number_elementwise_function!("ceil", self.children, vars, n, n.ceil())
+                    }
+                    "round" => {
+                        eval_number_elementwise_function!("round", self.children, vars, n, n.round())
+                    }
+                    "apply_unit" => {
+                        // apply_unit(5, "m/s") is 5|m/s| with a unit known only at runtime
+                        if self.children.len() == 2 {
+                            let n = match self.children[0].eval(vars) {
+                                RValue::Number(n) => n,
+                                other => { panic!("The 'apply_unit' function applies units to values of type 'Number' but an element of type '{}' was found.", other.get_type()); }
+                            };
+                            let unit_str: String = string_parameter(fname, self.children[1].eval(vars)).split_whitespace().collect();
+                            let (unit, factor, shift) = Unit::parse_unit_block(&unit_str);
+                            if n.unit == Unit::unitless() {
+                                let mut res = n.clone();
+                                res.unit = unit;
+                                res = res * factor;
+                                res.re -= shift;
+                                RValue::Number(res)
+                            }else{
+                                panic!("Applying units is allowed only on unitless values but '{}' was found", n)
+                            }
+                        }else{
+                            panic!("The 'apply_unit' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "same_unit" => {
+                        eval_number_binary_function!("same_unit", self.children, vars, n0, n1, n0.unit == n1.unit)
+                    }
+                    "round_to" => {
+                        eval_number_binary_function!("round_to", self.children, vars, n0, n1, {
+                            if n0.unit != n1.unit { panic!("The 'round_to' function operates on quantities with the same units but '{n0}' and '{n1}' were found.") }
+                            if !n0.is_real() { panic!("The 'round_to' function operates on real quantities but '{n0}' was found.") }
+                            if !n1.is_real() || n1.re == 0.0 { panic!("The 'round_to' function needs a real non zero step but '{n1}' was found.") }
+                            n0.round_to(&n1)
+                        })
+                    }
+                    "measure" => {
+                        // measure(value, sigma) is value pm sigma
+                        eval_number_binary_function!("measure", self.children, vars, n0, n1, {
+                            if n0.unit != n1.unit { panic!("The 'measure' function operates on a value and an uncertainty with the same units but '{n0}' and '{n1}' were found.") }
+                            let mut res = n0.clone();
+                            res.vre = n1.re*n1.re;
+                            res.vim = n1.im*n1.im;
+                            res
+                        })
+                    }
+                    "round_uncertainty" => {
+                        // round_uncertainty(x) or round_uncertainty(x, significant_figures)
+                        if self.children.len() == 1 || self.children.len() == 2 {
+                            let figures = if self.children.len() == 2 { count_parameter("round_uncertainty", self.children[1].eval(vars)) } else { 1 };
+                            if figures == 0 || figures > 15 {
+                                panic!("The 'round_uncertainty' function keeps between 1 and 15 significant figures but {figures} were asked.");
+                            }
+                            match self.children[0].eval(vars) {
+                                RValue::Number(n) => RValue::Number(n.round_uncertainty(figures as u32)),
+                                other => {
+                                    panic!("The 'round_uncertainty' function takes a value of type 'Number' but an element of type '{}' was found.", other.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'round_uncertainty' function takes 1 or 2 parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "hypot" => {
+                        eval_number_binary_function!("hypot", self.children, vars, n0, n1, {
+                            if n0.unit != n1.unit { panic!("The 'hypot' function operates on quantities with the same units but '{n0}' and '{n1}' were found.") }
+                            if !n0.is_real() || !n1.is_real() { panic!("The 'hypot' function operates on real quantities but '{n0}' and '{n1}' were found.") }
+                            n0.hypot(&n1)
+                        })
+                    }
+                    "divmod" => {
+                        if self.children.len() == 2 {
+                            let childval0 = self.children[0].eval(vars);
+                            let childval1 = self.children[1].eval(vars);
+                            match (childval0, childval1) {
+                                (RValue::Number(a), RValue::Number(b)) => {
+                                    if !a.is_real() || !b.is_real() { panic!("The 'divmod' function operates on real quantities but '{a}' and '{b}' were found.") }
+                                    // a = b*q + r with q integer
+                                    let mut q = a.clone().value() / b.clone().value();
+                                    q.re = q.re.floor();
+                                    let r = a - b * q.clone();
+                                    RValue::Matrix(2, 1, vec![RValue::Number(q), RValue::Number(r)])
+                                }
+                                (v0, v1) => {
+                                    panic!("The 'divmod' function takes two values of type 'Number' but elements of type '{}' and '{}' were found.", v0.get_type(), v1.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'divmod' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
                     "max" => {
                         eval_number_binary_function!("max", self.children, vars, n0, n1, {
                             if n0.unit != n1.unit { panic!("The 'max' function operates on quantities with the same units but '{n0}' and '{n1}' were found.") }
@@ -531,199 +1312,939 @@ impl Tree {
                             n0.min(&n1)
                         })
                     }
-                    // VOID FUNCTIONS
-                    "write" => {
-                        if self.children.len() > 0 {
-                            for v in self.children.iter() {
-                                print!("{}", v.eval(vars));
-                            }
-                            RValue::Void
-                        }else{                        
-                            panic!("The 'write' function takes one or more parameters but no parameters were found.")
+                    "head" | "tail" => {
+                        if self.children.len() == 2 {
+                            let cells = vector_cells(fname, self.children[0].eval(vars));
+                            let n = count_parameter(fname, self.children[1].eval(vars)).min(cells.len());
+                            let res: Vec<RValue> = if fname == "head" {
+                                cells[..n].to_vec()
+                            }else{
+                                cells[cells.len() - n..].to_vec()
+                            };
+                            RValue::Matrix(1, n, res)
+                        }else{
+                            panic!("The '{}' function takes two parameters, but {} parameters were found.", fname, self.children.len());
                         }
                     }
-                    "print" => {
-                        if self.children.len() > 0 {
-                            for v in self.children.iter() {
-                                print!("{} ", v.eval(vars));
-                            }
-                            print!("\n");
-                            RValue::Void
-                        }else{                        
-                            panic!("The 'print' function takes one or more parameters but no parameters were found.")
+                    "upper" | "lower" | "trim" => {
+                        if self.children.len() == 1 {
+                            let text = string_parameter(fname, self.children[0].eval(vars));
+                            RValue::String(match &fname[..] {
+                                "upper" => text.to_uppercase(),
+                                "lower" => text.to_lowercase(),
+                                _ => text.trim().to_owned(),
+                            })
+                        }else{
+                            panic!("The '{}' function takes one parameter, but {} parameters were found.", fname, self.children.len());
                         }
                     }
-                    "assert" => {
-                        if self.children.len() == 1 || self.children.len() == 2 {
-                            let v = self.children[0].eval(vars);
-                            let mut should_panic = false;
-                            match v {
-                                RValue::Void => {
-                                    should_panic = true;
+                    "contains" | "index_of" => {
+                        if self.children.len() == 2 {
+                            let haystack = self.children[0].eval(vars);
+                            let needle = self.children[1].eval(vars);
+                            // 1-based position of the needle, 0 when absent
+                            let position: usize = match haystack {
+                                RValue::String(text) => {
+                                    let sub = string_parameter(fname, needle);
+                                    match text.find(&sub[..]) {
+                                        Some(byte_index) => text[..byte_index].graphemes(true).count() + 1,
+                                        None => 0,
+                                    }
                                 }
-                                RValue::Number(n) => {
-                                    if n.re != 1.0 || n.im != 0.0 || n.vre != 0.0 || n.vim != 0.0 {
-                                        should_panic = true;
+                                matrix => {
+                                    let cells = vector_cells(fname, matrix);
+                                    match cells.iter().position(|cell| values_equal(cell, &needle)) {
+                                        Some(index) => index + 1,
+                                        None => 0,
                                     }
                                 }
-                                RValue::String(_) => { should_panic = true; }
-                                RValue::Matrix(_, _, _) => { should_panic = true; }
+                            };
+                            if fname == "contains" {
+                                RValue::Bool(position != 0)
+                            }else{
+                                RValue::Number((position as f64).into())
                             }
-                            if should_panic {
-                                if self.children.len() == 2 {
-                                    panic!("{}", self.children[1].eval(vars));
-                                }else{
-                                    panic!();
+                        }else{
+                            panic!("The '{}' function takes two parameters, but {} parameters were found.", fname, self.children.len());
+                        }
+                    }
+                    "split" => {
+                        if self.children.len() == 2 {
+                            let text = string_parameter(fname, self.children[0].eval(vars));
+                            let delimiter = string_parameter(fname, self.children[1].eval(vars));
+                            if delimiter.is_empty() { panic!("The 'split' function needs a non empty delimiter."); }
+                            let pieces: Vec<RValue> = text.split(&delimiter[..]).map(|p| RValue::String(p.to_owned())).collect();
+                            RValue::Matrix(1, pieces.len(), pieces)
+                        }else{
+                            panic!("The 'split' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "join" => {
+                        if self.children.len() == 2 {
+                            let matrix = self.children[0].eval(vars);
+                            let separator = string_parameter(fname, self.children[1].eval(vars));
+                            match matrix {
+                                RValue::Matrix(_, _, v) => {
+                                    let pieces: Vec<String> = v.iter().map(|cell| format!("{cell}")).collect();
+                                    RValue::String(pieces.join(&separator))
+                                }
+                                other => {
+                                    panic!("The 'join' function takes a matrix as first parameter but an element of type '{}' was found.", other.get_type());
                                 }
                             }
-                            RValue::Void
-                        }else{                        
-                            panic!("The 'assert' function takes one or two parameters but {} parameters were found.", self.children.len())
+                        }else{
+                            panic!("The 'join' function takes two parameters, but {} parameters were found.", self.children.len());
                         }
                     }
-                    "error" => {
+                    // MATRIX REDUCTIONS
+                    "sum" => {
                         if self.children.len() == 1 {
-                            panic!("{}", self.children[0].eval(vars));
-                        }else if self.children.len () == 0 {
-                            panic!();
+                            let cells = number_cells(fname, self.children[0].eval(vars));
+                            check_same_unit(fname, &cells);
+                            let mut cells = cells.into_iter();
+                            let first = cells.next().unwrap_or(0.0.into());
+                            RValue::Number(cells.fold(first, |acc, n| acc + n))
                         }else{
-                            panic!("The 'error' function takes one or two parameters but {} parameters were found.", self.children.len())
+                            panic!("The 'sum' function takes one parameter, but {} parameters were found.", self.children.len());
                         }
                     }
-                    _ => {
-                        panic!("Unknown function called '{}'", &fname);
+                    "nansum" | "nanmean" | "nanmin" | "nanmax" => {
+                        // like sum, mean, min and max of the cells, ignoring those whose real part is NaN
+                        if self.children.len() == 1 {
+                            let cells: Vec<Quantity> = number_cells(fname, self.children[0].eval(vars)).into_iter().filter(|n| !n.re.is_nan()).collect();
+                            check_same_unit(fname, &cells);
+                            if fname == "nansum" {
+                                let mut cells = cells.into_iter();
+                                let first = cells.next().unwrap_or(0.0.into());
+                                return RValue::Number(cells.fold(first, |acc, n| acc + n));
+                            }
+                            if cells.is_empty() { panic!("The '{fname}' function needs at least one cell which is not NaN.") }
+                            if fname == "nanmean" {
+                                let count = Quantity::real(cells.len() as f64);
+                                let mut cells = cells.into_iter();
+                                let first = cells.next().unwrap();
+                                return RValue::Number(cells.fold(first, |acc, n| acc + n) / count);
+                            }
+                            if let Some(n) = cells.iter().find(|n| !n.is_real()) {
+                                panic!("The '{fname}' function operates on real quantities but '{n}' was found.");
+                            }
+                            let mut best = 0;
+                            for k in 1..cells.len() {
+                                let better = if fname == "nanmin" { cells[k].re < cells[best].re } else { cells[k].re > cells[best].re };
+                                if better { best = k; }
+                            }
+                            RValue::Number(cells[best].clone())
+                        }else{
+                            panic!("The '{fname}' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
                     }
-                }
-            }
-            Node::Variable(varname) => {
-                if let Some(rvalue) = vars.get(varname) {
-                    (*rvalue).clone()
-                }else{
-                    panic!("Unable to give value to:\n {:?}", &self);
-                }
-            }
-            Node::Block => {
-                    let l = self.children.len();
-                    let mut res = RValue::Void;
-                    for i in 0..l {
-                        let value = self.children[i].eval(vars);
-                        if i == l - 1 {
-                            res = value;
+                    "prod" => {
+                        if self.children.len() == 1 {
+                            let cells = number_cells(fname, self.children[0].eval(vars));
+                            RValue::Number(cells.into_iter().fold(1.0.into(), |acc: Quantity, n| acc * n))
+                        }else{
+                            panic!("The 'prod' function takes one parameter, but {} parameters were found.", self.children.len());
                         }
                     }
-                    res
-            }
-            Node::UnitBlock(unit, factor, shift) => {
-                // assign this unit to this quantity
-                eval_number_unary_operator!("UnitBlock", self.children, vars, n0, {
-                    let mut res = n0.clone(); 
-                    if res.unit == Unit::unitless() {
-                        res.unit = unit.clone();
-                        res.re += shift;
-                        res = res * (*factor);
-                        res
-                    }else{
-                        panic!("Applying units is allowed only on unitless values but '{}' was found next to a unit block", res)
+                    "count" => {
+                        if self.children.len() == 1 {
+                            match self.children[0].eval(vars) {
+                                RValue::Matrix(_, _, v) => {
+                                    let n = v.iter().filter(|cell| is_nonzero_cell(fname, cell)).count();
+                                    RValue::Number((n as f64).into())
+                                }
+                                other => {
+                                    panic!("The 'count' function operates on matrices but an element of type '{}' was found.", other.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'count' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
                     }
-                })
-            }
-            Node::StringBlock(str) => {
-                let mut evaluated_string = String::with_capacity(str.len());
-                let chars = str.graphemes(true).collect::<Vec<&str>>();
-
-                let mut i = 0;
-                let mut last_slash = false;
-                while i < chars.len() {
-                    if chars[i] == "{" && !last_slash {
-                        if chars.len() == i + 1 {
-                            panic!("Opening '{{' inside string is missing a corresponding '}}': {str}");
-                        }
-                        let mut bcount = 1;
-                        let varname_from: usize = i + 1;
-                        let mut varname_to: usize = 0;
-                        let mut unit_from: usize = 0;
-                        let mut unit_to: usize = 0;
-                        i += 1;
-                        'bracketConsumer: while i < chars.len() {
-                            if chars[i] == "}" { 
-                                bcount -= 1;
-                                if bcount == 0 { break 'bracketConsumer; }
-                            }else if chars[i] == "{" {
-                                bcount += 1;
-                                i += 1;
-                                if bcount > 1 {
-                                    panic!("String block cannot contain nested brackets: '{str}'"); 
-                                }
-                            } else if chars[i] == "|" {
-                                // unit block
-                                if chars.len() == i + 1 {
-                                    panic!("Opening '|' inside string is missing a corresponding '|': {str}");
-                                }
-                                unit_from = i + 1;
-                                i += 1;
-                                'unitConsumer: while i < chars.len() {
-                                    if chars[i] == "|" {
-                                        unit_to = i - 1;
-                                        break 'unitConsumer;
+                    "any" | "all" => {
+                        if self.children.len() == 1 {
+                            match self.children[0].eval(vars) {
+                                RValue::Matrix(_, _, v) => {
+                                    let res = if fname == "any" {
+                                        v.iter().any(|cell| is_nonzero_cell(fname, cell))
                                     }else{
-                                        i += 1;                                        
-                                    }
+                                        v.iter().all(|cell| is_nonzero_cell(fname, cell))
+                                    };
+                                    RValue::Bool(res)
                                 }
-                                if unit_to == 0 {
-                                    panic!("String block cannot contain nested brackets: '{str}'"); 
+                                other => {
+                                    panic!("The '{}' function operates on matrices but an element of type '{}' was found.", fname, other.get_type());
                                 }
-                            } else if unit_to != 0 && chars[i] != " " {
-                                panic!("String block should finish with the name of the unit: '{str}'");
-                            } else if unit_to != 0 && chars[i] == " " {
-                                // just skip the space
-                            } else {
-                                varname_to = i;
                             }
-                            i += 1; 
+                        }else{
+                            panic!("The '{}' function takes one parameter, but {} parameters were found.", fname, self.children.len());
                         }
-                        if bcount != 0 {
-                            panic!("Opening '{{' inside string is missing a corresponding '}}': '{str}'");
+                    }
+                    "cumsum" => {
+                        if self.children.len() == 1 {
+                            let cells = vector_numbers(fname, self.children[0].eval(vars));
+                            check_same_unit(fname, &cells);
+                            let mut res: Vec<RValue> = Vec::with_capacity(cells.len());
+                            let mut acc: Option<Quantity> = None;
+                            for n in cells.into_iter() {
+                                let running = match acc { Some(a) => a + n, None => n };
+                                res.push(RValue::Number(running.clone()));
+                                acc = Some(running);
+                            }
+                            RValue::Matrix(1, res.len(), res)
                         }else{
-                            let varname: String = chars[varname_from..=varname_to].join("");
-                            if let Some(rvalue) = vars.get(varname.trim()) {
-                                let unit_full_string: String = chars[unit_from..=unit_to].join("");
-                                let unit_string: String = if unit_to > 0 {
-                                    unit_full_string.trim().to_owned()
-                                } else {
-                                    String::new()
-                                };
-                                let formated_variable_value = match rvalue {
-                                    RValue::Number(q) => {
-                                        q.to_text(unit_string)
+                            panic!("The 'cumsum' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "diff" => {
+                        if self.children.len() == 1 {
+                            let cells = vector_numbers(fname, self.children[0].eval(vars));
+                            check_same_unit(fname, &cells);
+                            if cells.len() < 2 { panic!("The 'diff' function needs a vector of at least two elements but {} were found.", cells.len()); }
+                            let res: Vec<RValue> = cells.windows(2).map(|w| RValue::Number(w[1].clone() - w[0].clone())).collect();
+                            RValue::Matrix(1, res.len(), res)
+                        }else{
+                            panic!("The 'diff' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    // THREE PARAMETERS FUNCTIONS
+                    "between" => {
+                        // between(x, low, high) is low <= x <= high
+                        eval_number_ternary_function!("between", self.children, vars, x, low, high, {
+                            if x.unit != low.unit || x.unit != high.unit {
+                                panic!("The 'between' function operates on quantities with the same units but '{x}', '{low}' and '{high}' were found.");
+                            }
+                            if !x.is_real() || !low.is_real() || !high.is_real() {
+                                panic!("The 'between' function operates on real quantities but '{x}', '{low}' and '{high}' were found.");
+                            }
+                            if low.re > high.re { panic!("The 'between' function needs a lower bound not greater than the upper one but '{low}' and '{high}' were found.") }
+                            low.re <= x.re && x.re <= high.re
+                        })
+                    }
+                    "lerp" => {
+                        eval_number_ternary_function!("lerp", self.children, vars, a, b, t, {
+                            if a.unit != b.unit { panic!("The 'lerp' function interpolates between quantities with the same units but '{a}' and '{b}' were found.") }
+                            if !t.unit.is_unitless() { panic!("The 'lerp' function takes a unitless interpolation parameter but '{t}' was found.") }
+                            a.clone() + (b - a) * t
+                        })
+                    }
+                    // VOID FUNCTIONS
+                    "zipmap" => {
+                        // zipmap(a, b, "name") applies a binary operator or function cell by cell
+                        if self.children.len() == 3 {
+                            let a = self.children[0].eval(vars);
+                            let b = self.children[1].eval(vars);
+                            let name = string_parameter(fname, self.children[2].eval(vars));
+                            match (a, b) {
+                                (RValue::Matrix(w0, h0, v0), RValue::Matrix(w1, h1, v1)) => {
+                                    if w0 != w1 || h0 != h1 {
+                                        panic!("The 'zipmap' function operates on matrices with the same shape but a '{h0}×{w0}' and a '{h1}×{w1}' matrix were found.");
                                     }
-                                    _ => {
-                                        format!("{}", (*rvalue))
+                                    let mut res = Vec::with_capacity(v0.len());
+                                    for (x, y) in v0.into_iter().zip(v1) {
+                                        res.push(call_by_name(fname, &name, vec![x, y]));
                                     }
-                                };
-                                evaluated_string.push_str(&formated_variable_value);
-                                i += 1;
-                            }else{
-                                panic!("Unable to give value to string block due to unknown variable: '{}'", varname.trim());
+                                    RValue::Matrix(w0, h0, res)
+                                }
+                                (a, b) => {
+                                    panic!("The 'zipmap' function operates on two matrices but elements of type '{}' and '{}' were found.", a.get_type(), b.get_type());
+                                }
                             }
+                        }else{
+                            panic!("The 'zipmap' function takes 3 parameters, but {} parameters were found.", self.children.len());
                         }
-                    }else if chars[i] == "{" && last_slash {
-                        evaluated_string.push('{');
-                        last_slash = false;
-                        i += 1;
-                    }else if chars[i] == "\\" && !last_slash {
-                        last_slash = true;
-                        i += 1;
-                    } else if chars[i] == "\\" && last_slash {
-                        last_slash = false;
-                        evaluated_string.push('\\');
-                        i += 1;
-                    } else {
-                        last_slash = false;
-                        evaluated_string.push_str(chars[i]);
-                        i += 1;
                     }
-                }
-
+                    "reduce" => {
+                        // reduce(m, init, "name") folds left over the cells in row-major order
+                        if self.children.len() == 3 {
+                            let m = self.children[0].eval(vars);
+                            let mut acc = self.children[1].eval(vars);
+                            let name = string_parameter(fname, self.children[2].eval(vars));
+                            match m {
+                                RValue::Matrix(_, _, v) => {
+                                    for x in v.into_iter() {
+                                        acc = call_by_name(fname, &name, vec![acc, x]);
+                                    }
+                                    acc
+                                }
+                                other => {
+                                    panic!("The 'reduce' function operates on matrices but an element of type '{}' was found.", other.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'reduce' function takes 3 parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "diag" => {
+                        // the diagonal of a square matrix or the diagonal matrix of a vector
+                        if self.children.len() == 1 {
+                            match self.children[0].eval(vars) {
+                                RValue::Matrix(w, h, v) if w == h => {
+                                    let res: Vec<RValue> = (0..w).map(|k| v[k*w + k].clone()).collect();
+                                    RValue::Matrix(1, w, res)
+                                }
+                                RValue::Matrix(w, h, v) if w == 1 || h == 1 => {
+                                    let n = v.len();
+                                    // the zeros share the unit of the diagonal
+                                    let zero = match &v[0] {
+                                        RValue::Number(q) => RValue::Number(Quantity::with_unit(0.0, q.unit.clone())),
+                                        other => { panic!("The 'diag' function operates on numbers but an element of type '{}' was found.", other.get_type()); }
+                                    };
+                                    let mut res = vec![zero; n*n];
+                                    for (k, cell) in v.into_iter().enumerate() {
+                                        res[k*n + k] = cell;
+                                    }
+                                    RValue::Matrix(n, n, res)
+                                }
+                                RValue::Matrix(w, h, _) => {
+                                    panic!("The 'diag' function operates on square matrices or vectors but a '{h}×{w}' matrix was found.");
+                                }
+                                other => {
+                                    panic!("The 'diag' function operates on matrices but an element of type '{}' was found.", other.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'diag' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "trace" => {
+                        if self.children.len() == 1 {
+                            match self.children[0].eval(vars) {
+                                RValue::Matrix(w, h, v) if w == h => {
+                                    let cells = number_cells(fname, RValue::Matrix(1, w, (0..w).map(|k| v[k*w + k].clone()).collect()));
+                                    check_same_unit(fname, &cells);
+                                    let mut cells = cells.into_iter();
+                                    let first = cells.next().unwrap_or(0.0.into());
+                                    RValue::Number(cells.fold(first, |acc, n| acc + n))
+                                }
+                                RValue::Matrix(w, h, _) => {
+                                    panic!("The 'trace' function operates on square matrices but a '{h}×{w}' matrix was found.");
+                                }
+                                other => {
+                                    panic!("The 'trace' function operates on matrices but an element of type '{}' was found.", other.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'trace' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "rank" => {
+                        // computed on the real parts of the values
+                        if self.children.len() == 1 {
+                            let value = self.children[0].eval(vars);
+                            let (w, h) = match &value {
+                                RValue::Matrix(w, h, _) => (*w, *h),
+                                other => { panic!("The 'rank' function operates on matrices but an element of type '{}' was found.", other.get_type()); }
+                            };
+                            let cells = number_cells(fname, value);
+                            RValue::Number(Quantity::real(rank(w, h, cells.iter().map(|n| n.re).collect()) as f64))
+                        }else{
+                            panic!("The 'rank' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "solve" => {
+                        // solve(A, b) is the solution x of the linear system A x = b
+                        if self.children.len() == 2 {
+                            let a = self.children[0].eval(vars);
+                            let n = match &a {
+                                RValue::Matrix(w, h, _) if w == h => *w,
+                                RValue::Matrix(w, h, _) => { panic!("The 'solve' function needs a square matrix but a '{h}×{w}' matrix was found."); }
+                                other => { panic!("The 'solve' function operates on matrices but an element of type '{}' was found.", other.get_type()); }
+                            };
+                            let a = number_cells(fname, a);
+                            let b = vector_numbers(fname, self.children[1].eval(vars));
+                            if b.len() != n {
+                                panic!("The 'solve' function needs a vector with {n} elements for a '{n}×{n}' matrix but {} elements were found.", b.len());
+                            }
+                            let x = solve_linear(fname, n, a, b);
+                            RValue::Matrix(1, n, x.into_iter().map(RValue::Number).collect())
+                        }else{
+                            panic!("The 'solve' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "argmin" | "argmax" => {
+                        // 1-based linear index of the first smallest/largest cell
+                        if self.children.len() == 1 {
+                            let cells = number_cells(fname, self.children[0].eval(vars));
+                            if cells.is_empty() { panic!("The '{fname}' function operates on non empty matrices.") }
+                            if let Some(n) = cells.iter().find(|n| !n.is_real()) {
+                                panic!("The '{fname}' function operates on real quantities but '{n}' was found.");
+                            }
+                            check_same_unit(fname, &cells);
+                            let mut best = 0;
+                            for k in 1..cells.len() {
+                                let better = if fname == "argmin" { cells[k].re < cells[best].re } else { cells[k].re > cells[best].re };
+                                if better { best = k; }
+                            }
+                            RValue::Number(Quantity::real((best + 1) as f64))
+                        }else{
+                            panic!("The '{fname}' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "unique" => {
+                        // column vector of the distinct values in order of first occurrence
+                        if self.children.len() == 1 {
+                            let cells = vector_numbers(fname, self.children[0].eval(vars));
+                            if let Some(n) = cells.iter().find(|n| !n.is_real()) {
+                                panic!("The 'unique' function operates on real quantities but '{n}' was found.");
+                            }
+                            check_same_unit(fname, &cells);
+                            let mut res: Vec<Quantity> = Vec::new();
+                            for n in cells.into_iter() {
+                                if !res.iter().any(|m| m.re == n.re) {
+                                    res.push(n);
+                                }
+                            }
+                            RValue::Matrix(1, res.len(), res.into_iter().map(RValue::Number).collect())
+                        }else{
+                            panic!("The 'unique' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "median" => {
+                        if self.children.len() == 1 {
+                            let (reals, unit) = sorted_reals(fname, self.children[0].eval(vars));
+                            let n = reals.len();
+                            let median = if n % 2 == 1 { reals[n/2] } else { (reals[n/2 - 1] + reals[n/2]) / 2.0 };
+                            RValue::Number(Quantity::with_unit(median, unit))
+                        }else{
+                            panic!("The 'median' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "polyval" => {
+                        // polyval([a, b, c], x) = a x² + b x + c, coefficients from the highest degree
+                        if self.children.len() == 2 {
+                            let coefficients = vector_numbers(fname, self.children[0].eval(vars));
+                            if coefficients.is_empty() { panic!("The 'polyval' function needs at least one coefficient.") }
+                            let x = match self.children[1].eval(vars) {
+                                RValue::Number(x) => x,
+                                other => { panic!("The 'polyval' function takes a value of type 'Number' but an element of type '{}' was found.", other.get_type()); }
+                            };
+                            let res = x.polyval(&coefficients);
+                            RValue::Number(res)
+                        }else{
+                            panic!("The 'polyval' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "roots" => {
+                        // roots of a linear or quadratic polynomial, coefficients from the highest degree
+                        if self.children.len() == 1 {
+                            let coefficients = vector_numbers(fname, self.children[0].eval(vars));
+                            if coefficients.len() < 2 || coefficients.len() > 3 {
+                                panic!("The 'roots' function only supports polynomials of degree 1 or 2 but {} coefficients were found.", coefficients.len());
+                            }
+                            let a = &coefficients[0];
+                            if a.re == 0.0 && a.im == 0.0 { panic!("The 'roots' function needs a non zero leading coefficient.") }
+                            let expected = coefficients[1].unit.clone() / a.unit.clone();
+                            if coefficients.len() == 3 && coefficients[2].unit != expected.clone() * coefficients[1].unit.clone() {
+                                panic!("The 'roots' function found coefficients with incompatible units: '{}', '{}' and '{}'.", coefficients[0], coefficients[1], coefficients[2]);
+                            }
+                            let roots = if coefficients.len() == 2 {
+                                vec![-(coefficients[1].clone() / a.clone())]
+                            }else{
+                                let (r1, r2) = Quantity::quadratic_roots(a, &coefficients[1], &coefficients[2]);
+                                vec![r1, r2]
+                            };
+                            // adding zero turns -0 into 0, which prints better
+                            let roots: Vec<RValue> = roots.into_iter().map(|r| RValue::Number(Quantity { re: r.re + 0.0, im: r.im + 0.0, ..r })).collect();
+                            RValue::Matrix(1, roots.len(), roots)
+                        }else{
+                            panic!("The 'roots' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "covariance" | "correlation" => {
+                        // sample covariance and pearson correlation of two data vectors
+                        if self.children.len() == 2 {
+                            let (x, unit_x) = vector_reals(fname, self.children[0].eval(vars));
+                            let (y, unit_y) = vector_reals(fname, self.children[1].eval(vars));
+                            if x.len() != y.len() {
+                                panic!("The '{fname}' function operates on vectors with the same length but lengths {} and {} were found.", x.len(), y.len());
+                            }
+                            if x.len() < 2 { panic!("The '{fname}' function needs at least two data points.") }
+                            let cov = covariance(&x, &y);
+                            if fname == "covariance" {
+                                RValue::Number(Quantity::with_unit(cov, unit_x * unit_y))
+                            }else{
+                                let spread = (covariance(&x, &x) * covariance(&y, &y)).sqrt();
+                                if spread == 0.0 { panic!("The 'correlation' function is undefined for data with zero variance.") }
+                                // rounding can push a perfect correlation slightly past 1
+                                RValue::Number((cov / spread).clamp(-1.0, 1.0).into())
+                            }
+                        }else{
+                            panic!("The '{fname}' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "percentile" | "quantile" => {
+                        // percentile(v, p) with p in [0, 100], quantile(v, q) with q in [0, 1]
+                        // linearly interpolating between the sorted values
+                        if self.children.len() == 2 {
+                            let (reals, unit) = sorted_reals(fname, self.children[0].eval(vars));
+                            let scale = if fname == "percentile" { 100.0 } else { 1.0 };
+                            let p = match self.children[1].eval(vars) {
+                                RValue::Number(p) if p.is_real() && p.unit.is_unitless() && p.re >= 0.0 && p.re <= scale => p.re / scale,
+                                other => { panic!("The '{fname}' function expects a pure real number between 0 and {scale} but '{other}' was found."); }
+                            };
+                            let position = p * ((reals.len() - 1) as f64);
+                            let below = position.floor() as usize;
+                            let above = position.ceil() as usize;
+                            let value = reals[below] + (reals[above] - reals[below]) * (position - below as f64);
+                            RValue::Number(Quantity::with_unit(value, unit))
+                        }else{
+                            panic!("The '{fname}' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "histogram" => {
+                        // histogram(data, bins) or histogram(data, bins, [low, high])
+                        // returns a bins×2 matrix with a row for each bin: its center and its count
+                        if self.children.len() == 2 || self.children.len() == 3 {
+                            let data = vector_numbers(fname, self.children[0].eval(vars));
+                            let bins = count_parameter(fname, self.children[1].eval(vars));
+                            if bins < 1 { panic!("The 'histogram' function needs at least one bin.") }
+                            if data.iter().any(|n| !n.is_real()) { panic!("The 'histogram' function operates on real quantities.") }
+                            check_same_unit(fname, &data);
+                            let unit = data.first().map(|n| n.unit.clone()).unwrap_or(Unit::unitless());
+                            let (mut low, mut high) = if self.children.len() == 3 {
+                                let bounds = vector_numbers(fname, self.children[2].eval(vars));
+                                if bounds.len() != 2 || !bounds[0].is_real() || !bounds[1].is_real() || bounds[0].re > bounds[1].re {
+                                    panic!("The 'histogram' function expects its range as a vector [low, high] with low <= high.");
+                                }
+                                if !data.is_empty() && bounds[0].unit != unit {
+                                    panic!("The 'histogram' function needs a range with the same units of the data but '{}' and '{}' were found.", bounds[0], data[0]);
+                                }
+                                (bounds[0].re, bounds[1].re)
+                            }else{
+                                (data.iter().fold(f64::INFINITY, |m, n| m.min(n.re)), data.iter().fold(f64::NEG_INFINITY, |m, n| m.max(n.re)))
+                            };
+                            if data.is_empty() && self.children.len() == 2 { low = 0.0; high = 1.0; }
+                            if low == high { low -= 0.5; high += 0.5; }
+                            let width = (high - low) / (bins as f64);
+                            let mut counts = vec![0usize; bins];
+                            for n in data.iter() {
+                                if n.re >= low && n.re <= high {
+                                    // the last bin includes its right edge
+                                    let k = (((n.re - low) / width) as usize).min(bins - 1);
+                                    counts[k] += 1;
+                                }
+                            }
+                            let mut res = Vec::with_capacity(2*bins);
+                            for (k, count) in counts.into_iter().enumerate() {
+                                res.push(RValue::Number(Quantity::with_unit(low + width*(k as f64 + 0.5), unit.clone())));
+                                res.push(RValue::Number(Quantity::real(count as f64)));
+                            }
+                            RValue::Matrix(2, bins, res)
+                        }else{
+                            panic!("The 'histogram' function takes 2 or 3 parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "kron" => {
+                        // kronecker product: each cell of a scales a copy of b
+                        if self.children.len() == 2 {
+                            let (aw, ah, a) = number_matrix(fname, self.children[0].eval(vars));
+                            let (bw, bh, b) = number_matrix(fname, self.children[1].eval(vars));
+                            let (w, h) = (aw*bw, ah*bh);
+                            let mut res = Vec::with_capacity(w*h);
+                            for row in 0..h {
+                                for col in 0..w {
+                                    let x = a[(row / bh)*aw + col / bw].clone();
+                                    let y = b[(row % bh)*bw + col % bw].clone();
+                                    res.push(RValue::Number(x * y));
+                                }
+                            }
+                            RValue::Matrix(w, h, res)
+                        }else{
+                            panic!("The 'kron' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "outer" => {
+                        // outer product: cell (i, j) is a[i]*b[j]
+                        if self.children.len() == 2 {
+                            let a = vector_numbers(fname, self.children[0].eval(vars));
+                            let b = vector_numbers(fname, self.children[1].eval(vars));
+                            let mut res = Vec::with_capacity(a.len()*b.len());
+                            for x in a.iter() {
+                                for y in b.iter() {
+                                    res.push(RValue::Number(x.clone() * y.clone()));
+                                }
+                            }
+                            RValue::Matrix(b.len(), a.len(), res)
+                        }else{
+                            panic!("The 'outer' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "tile" | "repmat" => {
+                        // tile(m, rows, cols) repeats m rows times vertically and cols times horizontally
+                        if self.children.len() == 3 {
+                            let (w, h, v) = match self.children[0].eval(vars) {
+                                RValue::Matrix(w, h, v) => (w, h, v),
+                                other => { panic!("The '{fname}' function operates on matrices but an element of type '{}' was found.", other.get_type()); }
+                            };
+                            let rows = count_parameter(fname, self.children[1].eval(vars));
+                            let cols = count_parameter(fname, self.children[2].eval(vars));
+                            if rows == 0 || cols == 0 {
+                                panic!("The '{fname}' function needs positive repetition counts but '{rows}' and '{cols}' were found.");
+                            }
+                            let mut res = Vec::with_capacity(w*h*rows*cols);
+                            for row in 0..(h*rows) {
+                                for col in 0..(w*cols) {
+                                    res.push(v[(row % h)*w + col % w].clone());
+                                }
+                            }
+                            RValue::Matrix(w*cols, h*rows, res)
+                        }else{
+                            panic!("The '{fname}' function takes 3 parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "flatten" => {
+                        // column vector of the cells in row-major order, the order of linear indexing
+                        if self.children.len() == 1 {
+                            match self.children[0].eval(vars) {
+                                RValue::Matrix(_, _, v) => RValue::Matrix(1, v.len(), v),
+                                other => { panic!("The 'flatten' function operates on matrices but an element of type '{}' was found.", other.get_type()); }
+                            }
+                        }else{
+                            panic!("The 'flatten' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "zip" => {
+                        // zip(a, b) is the n×2 matrix whose rows are the pairs of cells of a and b
+                        if self.children.len() == 2 {
+                            let a = vector_cells(fname, self.children[0].eval(vars));
+                            let b = vector_cells(fname, self.children[1].eval(vars));
+                            if a.len() != b.len() {
+                                panic!("The 'zip' function operates on vectors with the same length but lengths {} and {} were found.", a.len(), b.len());
+                            }
+                            let n = a.len();
+                            let mut res = Vec::with_capacity(2*n);
+                            for (x, y) in a.into_iter().zip(b) {
+                                res.push(x);
+                                res.push(y);
+                            }
+                            RValue::Matrix(2, n, res)
+                        }else{
+                            panic!("The 'zip' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "swap" => {
+                        if self.children.len() == 2 {
+                            let mut names = Vec::with_capacity(2);
+                            for child in self.children.iter() {
+                                match &child.node {
+                                    Node::Variable(varname) => {
+                                        if !vars.contains_key(varname) { panic!("The 'swap' function can't swap '{varname}' because it is not an existing variable."); }
+                                        names.push(varname.clone());
+                                    }
+                                    _ => { panic!("The 'swap' function takes two variable names but {:?} was found.", child); }
+                                }
+                            }
+                            if names[0] != names[1] {
+                                let value0 = vars.remove(&names[0]).unwrap();
+                                let value1 = vars.insert(names[1].clone(), value0).unwrap();
+                                vars.insert(names[0].clone(), value1);
+                            }
+                            RValue::Void
+                        }else{
+                            panic!("The 'swap' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "undef" => {
+                        // undef(x) forgets the variable x, forgetting an unknown variable is an error
+                        if self.children.len() == 1 {
+                            match &self.children[0].node {
+                                Node::Variable(varname) => {
+                                    if vars.remove(varname).is_none() {
+                                        panic!("The 'undef' function can't remove '{varname}' because it is not an existing variable.");
+                                    }
+                                }
+                                _ => { panic!("The 'undef' function takes a variable name but {:?} was found.", self.children[0]); }
+                            }
+                            RValue::Void
+                        }else{
+                            panic!("The 'undef' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "write" => {
+                        if !self.children.is_empty() {
+                            for v in self.children.iter() {
+                                // values without a value, like the result of 'if 0 {1}', print nothing
+                                match v.eval(vars) {
+                                    RValue::Void => {}
+                                    value => { print!("{value}"); }
+                                }
+                            }
+                            RValue::Void
+                        }else{                        
+                            panic!("The 'write' function takes one or more parameters but no parameters were found.")
+                        }
+                    }
+                    "print" => {
+                        if !self.children.is_empty() {
+                            // the parameters are separated by one space, without a trailing one
+                            // and Void parameters are skipped like in 'write'
+                            let mut line = Vec::with_capacity(self.children.len());
+                            for v in self.children.iter() {
+                                match v.eval(vars) {
+                                    RValue::Void => {}
+                                    value => { line.push(value.to_string()); }
+                                }
+                            }
+                            println!("{}", line.join(" "));
+                            RValue::Void
+                        }else{                        
+                            panic!("The 'print' function takes one or more parameters but no parameters were found.")
+                        }
+                    }
+                    "vars" => {
+                        // column vector with the sorted names of the defined variables
+                        if self.children.is_empty() {
+                            let res: Vec<RValue> = variable_names(vars).into_iter().map(RValue::String).collect();
+                            RValue::Matrix(1, res.len(), res)
+                        }else{
+                            panic!("The 'vars' function takes no parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "now" => {
+                        if self.children.is_empty() {
+                            let elapsed = START.get_or_init(std::time::Instant::now).elapsed();
+                            RValue::Number(Quantity::with_unit(elapsed.as_nanos() as f64 / 1e9, Unit { second: 1, ..Unit::unitless() }))
+                        }else{
+                            panic!("The 'now' function takes no parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "sleep" => {
+                        if self.children.len() == 1 {
+                            match self.children[0].eval(vars) {
+                                RValue::Number(n) => {
+                                    let valid_unit = n.unit.is_unitless() || n.unit == Unit { second: 1, ..Unit::unitless() };
+                                    if !valid_unit || !n.is_real() || n.re.is_nan() || n.re < 0.0 || n.re.is_infinite() {
+                                        panic!("The 'sleep' function takes a non negative duration in seconds but '{n}' was found.");
+                                    }
+                                    std::thread::sleep(std::time::Duration::from_secs_f64(n.re));
+                                    RValue::Void
+                                }
+                                other => {
+                                    panic!("The 'sleep' function takes a value of type 'Number' but an element of type '{}' was found.", other.get_type());
+                                }
+                            }
+                        }else{
+                            panic!("The 'sleep' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "env" => {
+                        if self.children.len() == 1 || self.children.len() == 2 {
+                            let name = string_parameter(fname, self.children[0].eval(vars));
+                            match std::env::var(&name) {
+                                Ok(value) => RValue::String(value),
+                                // unset variables give the default value if provided
+                                Err(_) => if self.children.len() == 2 { self.children[1].eval(vars) } else { RValue::Void },
+                            }
+                        }else{
+                            panic!("The 'env' function takes one or two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "readfile" => {
+                        if self.children.len() == 1 {
+                            let path = string_parameter(fname, self.children[0].eval(vars));
+                            match std::fs::read_to_string(&path) {
+                                Ok(content) => RValue::String(content),
+                                Err(e) => { panic!("Unable to read the file '{path}': {e}"); }
+                            }
+                        }else{
+                            panic!("The 'readfile' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "writefile" => {
+                        if self.children.len() == 2 {
+                            let path = string_parameter(fname, self.children[0].eval(vars));
+                            let content = format!("{}", self.children[1].eval(vars));
+                            if let Err(e) = std::fs::write(&path, content) {
+                                panic!("Unable to write to the file '{path}': {e}");
+                            }
+                            RValue::Void
+                        }else{
+                            panic!("The 'writefile' function takes two parameters, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "assert" => {
+                        if self.children.len() == 1 || self.children.len() == 2 {
+                            let v = self.children[0].eval(vars);
+                            let mut should_panic = false;
+                            match v {
+                                RValue::Void => {
+                                    should_panic = true;
+                                }
+                                RValue::Bool(b) => {
+                                    should_panic = !b;
+                                }
+                                RValue::Number(n) => {
+                                    if n.re != 1.0 || n.im != 0.0 || n.vre != 0.0 || n.vim != 0.0 {
+                                        should_panic = true;
+                                    }
+                                }
+                                RValue::String(_) => { should_panic = true; }
+                                RValue::Matrix(_, _, _) => { should_panic = true; }
+                            }
+                            if should_panic {
+                                if self.children.len() == 2 {
+                                    panic!("{}", self.children[1].eval(vars));
+                                }else{
+                                    panic!();
+                                }
+                            }
+                            RValue::Void
+                        }else{                        
+                            panic!("The 'assert' function takes one or two parameters but {} parameters were found.", self.children.len())
+                        }
+                    }
+                    "debug" => {
+                        // prints the internal representation and passes the value through
+                        if self.children.len() == 1 {
+                            let value = self.children[0].eval(vars);
+                            eprintln!("{:?}", value);
+                            value
+                        }else{
+                            panic!("The 'debug' function takes one parameter, but {} parameters were found.", self.children.len());
+                        }
+                    }
+                    "assert_eq" => {
+                        if self.children.len() == 2 || self.children.len() == 3 {
+                            let left = self.children[0].eval(vars);
+                            let right = self.children[1].eval(vars);
+                            if !values_equal(&left, &right) {
+                                if self.children.len() == 3 {
+                                    panic!("{}\n  left: {}\n right: {}", self.children[2].eval(vars), left, right);
+                                }else{
+                                    panic!("Assertion failed, the values are not equal\n  left: {}\n right: {}", left, right);
+                                }
+                            }
+                            RValue::Void
+                        }else{
+                            panic!("The 'assert_eq' function takes two or three parameters but {} parameters were found.", self.children.len())
+                        }
+                    }
+                    "error" => {
+                        if self.children.is_empty() {
+                            panic!();
+                        }else{
+                            // the parameters are concatenated as in 'write'
+                            let mut message = String::new();
+                            for v in self.children.iter() {
+                                message.push_str(&format!("{}", v.eval(vars)));
+                            }
+                            panic!("{}", message);
+                        }
+                    }
+                    _ => {
+                        panic!("Unknown function called '{}'", &fname);
+                    }
+                }
+            }
+            Node::Variable(varname) => {
+                if let Some(rvalue) = vars.get(varname) {
+                    (*rvalue).clone()
+                }else{
+                    panic!("Unable to give value to:\n {:?}", &self);
+                }
+            }
+            Node::Block => {
+                    let l = self.children.len();
+                    let mut res = RValue::Void;
+                    for i in 0..l {
+                        let value = self.children[i].eval(vars);
+                        if i == l - 1 {
+                            res = value;
+                        }
+                    }
+                    res
+            }
+            Node::UnitBlock(unit, factor, shift) => {
+                // assign this unit to this quantity
+                eval_number_unary_operator!("UnitBlock", self.children, vars, n0, {
+                    let mut res = n0.clone(); 
+                    if res.unit == Unit::unitless() {
+                        res.unit = unit.clone();
+                        // the value in SI units is value*factor - shift, the inverse of Quantity::to_text
+                        res = res * (*factor);
+                        res.re -= shift;
+                        res
+                    }else{
+                        panic!("Applying units is allowed only on unitless values but '{}' was found next to a unit block", res)
+                    }
+                })
+            }
+            Node::StringBlock(str) => {
+                let mut evaluated_string = String::with_capacity(str.len());
+                let chars = str.graphemes(true).collect::<Vec<&str>>();
+
+                let mut i = 0;
+                let mut last_slash = false;
+                while i < chars.len() {
+                    if chars[i] == "{" && !last_slash {
+                        // units cannot contain brackets, so the block ends at the first '}'
+                        let from = i + 1;
+                        let mut to = from;
+                        while to < chars.len() && chars[to] != "}" {
+                            if chars[to] == "{" {
+                                panic!("String block cannot contain nested brackets: '{str}'");
+                            }
+                            to += 1;
+                        }
+                        if to == chars.len() {
+                            panic!("Opening '{{' inside string is missing a corresponding '}}': '{str}'");
+                        }
+                        i = to + 1;
+                        let (expression, unit_string, format_spec) = split_string_block(&chars[from..to], str);
+                        let expression = expression.as_str();
+                        let is_name = expression.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '#');
+                        let value: Option<RValue> = if let Some(rvalue) = vars.get(expression) {
+                            Some(rvalue.clone())
+                        }else if is_name {
+                            None
+                        }else{
+                            // anything which is not a variable name is evaluated as an expression: "{a + b}"
+                            let mut lexer = Lexer::new();
+                            lexer.text = String::from(expression);
+                            lexer.lex();
+                            Some(crate::ast::ast(&lexer.lexems).eval(vars))
+                        };
+                        if let Some(rvalue) = value {
+                            let precision: Option<usize> = format_spec.as_ref().map(|spec| {
+                                match spec.trim().strip_prefix('.').and_then(|p| p.parse::<usize>().ok()) {
+                                    Some(p) => p,
+                                    None => { panic!("Unknown format specifier '{spec}' inside string, only precisions such as '.3' are allowed: '{str}'"); }
+                                }
+                            });
+                            let formated_variable_value = match rvalue {
+                                RValue::Number(q) => {
+                                    q.to_text_with_precision(unit_string, precision)
+                                }
+                                _ => {
+                                    if precision.is_some() {
+                                        panic!("Format specifiers inside strings can only be applied to numbers but '{}' is of type '{}'.", expression, rvalue.get_type());
+                                    }
+                                    format!("{}", rvalue)
+                                }
+                            };
+                            evaluated_string.push_str(&formated_variable_value);
+                        }else{
+                            panic!("Unable to give value to string block due to unknown variable: '{}'", expression);
+                        }
+                    }else if chars[i] == "{" && last_slash {
+                        evaluated_string.push('{');
+                        last_slash = false;
+                        i += 1;
+                    }else if chars[i] == "\\" && !last_slash {
+                        last_slash = true;
+                        i += 1;
+                    } else if chars[i] == "\\" && last_slash {
+                        last_slash = false;
+                        evaluated_string.push('\\');
+                        i += 1;
+                    } else {
+                        last_slash = false;
+                        evaluated_string.push_str(chars[i]);
+                        i += 1;
+                    }
+                }
+
                 RValue::String(evaluated_string)
             }
             Node::MatrixBlock(width, height) => {
@@ -738,22 +2259,11 @@ impl Tree {
                 RValue::Matrix(*width, *height, fields)
             }
             Node::MatrixIndexing(matrix_name) => {
-                let index0 = if self.children.len() > 0 { self.children[0].eval(vars) } else { RValue::Void };
+                let index0 = if !self.children.is_empty() { self.children[0].eval(vars) } else { RValue::Void };
                 let index1 = if self.children.len() > 1 { self.children[1].eval(vars) } else { RValue::Void };
 
                 let original_index_y: i64 = match index0 {
-                    RValue::Number(n) => {
-                        if n.im == 0.0 && n.vim == 0.0 && n.vre == 0.0 {
-                            let i = n.re.floor();
-                            if n.re == i && n.re != 0.0 {
-                                i as i64
-                            }else{
-                                panic!("Only pure, integer, non zero values are allowed when indexing a matrix but '{}' was found.", n);
-                            }
-                        } else{
-                            panic!("Only pure, integer, non zero values are allowed when indexing a matrix but '{}' was found.", n);
-                        }
-                    }
+                    RValue::Number(n) => n.as_index().unwrap_or_else(|e| panic!("{e}")),
                     other => {
                         panic!("Cannot index matrix with type '{}', '{}' was found.", other.get_type(), other);
                     }
@@ -763,36 +2273,31 @@ impl Tree {
                 if let Some(rvalue) = vars.get(matrix_name) {
                     match rvalue {
                         RValue::Matrix(w, h, v) => {
-                            if self.children.len() == 1 && *w == 1usize {
-                                let index_y = if original_index_y < 0 { (*h as i64) + original_index_y + 1} else { original_index_y } - 1;
-                                if index_y >= 0 && index_y < (*h as i64) { 
-                                    v[index_y as usize].clone()
+                            if self.children.len() == 1 {
+                                // linear indexing: cells are counted in row-major order,
+                                // so vectors are indexed the same way whatever their orientation
+                                // and [1,2;3,4][3] is 3
+                                let len = (*w * *h) as i64;
+                                let index = if original_index_y < 0 { len + original_index_y + 1} else { original_index_y } - 1;
+                                if index >= 0 && index < len { 
+                                    v[index as usize].clone()
                                 }else{
                                     panic!("Index must not exceed Matrix bounds. Matrix '{matrix_name}' is '{h}×{w}' but '{original_index_y}' was found.")
                                 }
                             }else if self.children.len() == 2 {
                                 let original_index_x: i64 = match index1 {
-                                    RValue::Number(n) => {
-                                        if n.im == 0.0 && n.vim == 0.0 && n.vre == 0.0 {
-                                            let i = n.re.floor();
-                                            if n.re == i && n.re != 0.0 {
-                                                i as i64
-                                            }else{
-                                                panic!("Only pure, integer, non zero values are allowed when indexing a matrix but '{}' was found.", n);
-                                            }
-                                        } else{
-                                            panic!("Only pure, integer, non zero values are allowed when indexing a matrix but '{}' was found.", n);
-                                        }
-                                    }
+                                    RValue::Number(n) => n.as_index().unwrap_or_else(|e| panic!("{e}")),
                                     other => {
                                         panic!("Cannot index matrix with type '{}'.", other.get_type());
                                     }
                                 };
                                 let index_x = if original_index_x < 0 { (*w as i64) + original_index_x + 1} else { original_index_x } - 1;
                                 let index_y = if original_index_y < 0 { (*h as i64) + original_index_y + 1} else { original_index_y } - 1;
-                                v[(index_y*(*w as i64) + index_x) as usize].clone()
-                            }else if self.children.len() == 1 && *w != 1usize {
-                                panic!("Cannot index a matrix using one index unless it is a column vector but {matrix_name} is '{h}×{w}' has '{h}' rows and '{w}' columns.");
+                                if index_y >= 0 && index_y < (*h as i64) && index_x >= 0 && index_x < (*w as i64) {
+                                    v[(index_y*(*w as i64) + index_x) as usize].clone()
+                                }else{
+                                    panic!("Index must not exceed Matrix bounds. Matrix '{matrix_name}' is '{h}×{w}' but '[{original_index_y}, {original_index_x}]' was found.")
+                                }
                             }else{
                                 panic!("Cannot index a matrix using '{}' indices", self.children.len());
                             }
@@ -813,4 +2318,1069 @@ impl Tree {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // evaluates a whole program, which must succeed
+    fn eval(code: &str) -> RValue {
+        Evaluator::eval_str(code).unwrap_or_else(|e| panic!("'{code}' failed with: {e}"))
+    }
+
+    // the message of the error raised by a program, which must fail
+    fn error(code: &str) -> String {
+        match Evaluator::eval_str(code) {
+            Ok(value) => panic!("'{code}' returned '{value}' instead of failing"),
+            Err(e) => e.message,
+        }
+    }
+
+    fn number(code: &str) -> Quantity {
+        match eval(code) {
+            RValue::Number(n) => n,
+            other => panic!("'{code}' returned '{other}' of type '{}' instead of a number", other.get_type()),
+        }
+    }
+
+    // the value of a real number without unit
+    fn real(code: &str) -> f64 {
+        let n = number(code);
+        assert!(n.is_real() && n.unit.is_unitless(), "'{code}' returned '{n}' instead of a real number without unit");
+        n.re
+    }
+
+    fn unit(text: &str) -> Unit {
+        Unit::parse(text).unwrap().0
+    }
+
+    // the value of a comparison
+    fn truth(code: &str) -> bool {
+        match eval(code) {
+            RValue::Bool(b) => b,
+            other => panic!("'{code}' returned '{other}' instead of a boolean"),
+        }
+    }
+
+    // the width, the height and the real cells of a matrix of numbers
+    fn matrix(code: &str) -> (usize, usize, Vec<f64>) {
+        match eval(code) {
+            RValue::Matrix(w, h, v) => (w, h, v.into_iter().map(|cell| match cell {
+                RValue::Number(n) => n.re,
+                other => panic!("'{code}' has a cell '{other}' which is not a number"),
+            }).collect()),
+            other => panic!("'{code}' returned '{other}' instead of a matrix"),
+        }
+    }
+
+    fn string(code: &str) -> String {
+        match eval(code) {
+            RValue::String(s) => s,
+            other => panic!("'{code}' returned '{other}' instead of a string"),
+        }
+    }
+
+    fn display(code: &str) -> String {
+        format!("{}", eval(code))
+    }
+
+    fn evaluator(code: &str) -> Evaluator {
+        let mut lexer = crate::lexer::Lexer::new();
+        lexer.text = String::from(code);
+        lexer.lex();
+        Evaluator::from_tree(crate::ast::ast(&lexer.lexems))
+    }
+
+    // a path in the temporary directory that no other test uses
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("tera_lang_{}_{name}", std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn lerp_interpolates_linearly() {
+        assert!(truth("lerp(0, 10, 0.25)==2.5"));
+        assert_eq!(real("lerp(0, 10, 0)"), 0.0);
+        assert_eq!(real("lerp(0, 10, 1)"), 10.0);
+        // extrapolates outside of [0, 1]
+        assert_eq!(real("lerp(0, 10, 2)"), 20.0);
+        let n = number("lerp(1|m|, 3|m|, 0.5)");
+        assert_eq!((n.re, n.unit), (2.0, unit("m")));
+    }
+
+    #[test]
+    fn lerp_checks_the_units() {
+        assert_eq!(error("lerp(1|m|, 3|s|, 0.5)"), "The 'lerp' function interpolates between quantities with the same units but '1m' and '3s' were found.");
+        assert_eq!(error("lerp(0, 10, 1|m|)"), "The 'lerp' function takes a unitless interpolation parameter but '1m' was found.");
+        assert_eq!(error("lerp(0, 10)"), "The 'lerp' function takes three parameters, but 2 parameters were found.");
+    }
+
+    #[test]
+    fn clamp01_clamps_to_the_unit_interval() {
+        assert!(truth("clamp01(1.5)==1"));
+        assert_eq!(real("clamp01(-0.5)"), 0.0);
+        assert_eq!(real("clamp01(0.25)"), 0.25);
+        assert_eq!(error("clamp01(2|m|)"), "The 'clamp01' function operates on unitless quantities but '2m' was found.");
+        assert_eq!(error("clamp01(2i)"), "The 'clamp01' function operates on real quantities but '0 + 2i' was found.");
+    }
+
+    #[test]
+    fn head_and_tail_take_the_ends_of_a_vector() {
+        assert_eq!(matrix("head([1,2,3,4], 2)"), (1, 2, vec![1.0, 2.0]));
+        assert_eq!(matrix("tail([1,2,3,4], 2)"), (1, 2, vec![3.0, 4.0]));
+        assert_eq!(matrix("head([1;2;3], 1)"), (1, 1, vec![1.0]));
+        // counts larger than the vector take all of it
+        assert_eq!(matrix("tail([1,2,3], 5)"), (1, 3, vec![1.0, 2.0, 3.0]));
+        assert_eq!(matrix("head([1,2,3], 0)"), (1, 0, vec![]));
+    }
+
+    #[test]
+    fn head_and_tail_reject_matrices_and_bad_counts() {
+        assert_eq!(error("head([1,2;3,4], 1)"), "The 'head' function operates on vectors but a matrix 2×2 was found.");
+        assert_eq!(error("tail([1,2,3], -1)"), "The 'tail' function expects a pure, non negative, integer count but '-1' was found.");
+        assert_eq!(error("head([1,2,3], 1.5)"), "The 'head' function expects a pure, non negative, integer count but '1.5' was found.");
+    }
+
+    #[test]
+    fn split_cuts_a_string_at_each_delimiter() {
+        assert_eq!(display("split(\"a,b,c\", \",\")"), "Matrix 3×1: [\"a\"; \"b\"; \"c\"]");
+        assert_eq!(display("split(\"abc\", \",\")"), "Matrix 1×1: [\"abc\"]");
+        assert_eq!(error("split(\"a\", \"\")"), "The 'split' function needs a non empty delimiter.");
+    }
+
+    #[test]
+    fn join_glues_the_cells_of_a_vector() {
+        assert_eq!(string("join([\"x\",\"y\"], \"-\")"), "x-y");
+        assert_eq!(string("join([1,2], \", \")"), "1, 2");
+        // join undoes split
+        assert_eq!(string("join(split(\"a,b,c\", \",\"), \",\")"), "a,b,c");
+    }
+
+    #[test]
+    fn upper_lower_and_trim_handle_unicode() {
+        assert_eq!(string("upper(\"abc\")"), "ABC");
+        assert_eq!(string("upper(\"ß\")"), "SS");
+        assert_eq!(string("lower(\"ÀB\")"), "àb");
+        assert_eq!(string("trim(\"  a b \\n\")"), "a b");
+        assert_eq!(error("upper(1)"), "The 'upper' function expects a value of type 'String' but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn contains_finds_substrings_and_cells() {
+        assert!(truth("contains(\"hello\", \"ell\")"));
+        assert!(!truth("contains(\"hello\", \"z\")"));
+        assert!(truth("contains([1|m|, 2|m|], 2|m|)"));
+        // units are part of the value
+        assert!(!truth("contains([1, 2], 2|m|)"));
+    }
+
+    #[test]
+    fn index_of_is_one_based_and_zero_when_missing() {
+        assert_eq!(real("index_of([10,20,30], 20)"), 2.0);
+        assert_eq!(real("index_of([10,20,30], 40)"), 0.0);
+        assert_eq!(real("index_of(\"hello\", \"l\")"), 3.0);
+        assert_eq!(real("index_of(\"hello\", \"z\")"), 0.0);
+    }
+
+    #[test]
+    fn variables_can_be_set_and_read_back() {
+        let mut evaluator = evaluator("{ speed = distance / 2|s|; 0 }");
+        evaluator.set_var(String::from("distance"), RValue::Number(Quantity { re: 10.0, im: 0.0, vre: 0.0, vim: 0.0, unit: unit("m") }));
+        evaluator.eval();
+        match evaluator.get_var("speed") {
+            Some(RValue::Number(n)) => assert_eq!((n.re, n.unit.clone()), (5.0, unit("m/s"))),
+            other => panic!("unexpected value of speed: {other:?}"),
+        }
+        assert_eq!(evaluator.get_var_display("speed").as_deref(), Some("5|m.s⁻¹|"));
+        assert!(evaluator.get_var("missing").is_none());
+        assert!(evaluator.get_var_display("missing").is_none());
+    }
+
+    #[test]
+    fn eval_str_returns_the_value_or_the_error() {
+        assert_eq!(real("2+3"), 5.0);
+        assert_eq!(error("foo(1)"), "Unknown function called 'foo'");
+        // one failure does not affect the next evaluation
+        assert_eq!(real("2*3"), 6.0);
+    }
+
+    #[test]
+    fn value_si_and_to_si_express_values_in_base_units() {
+        assert_eq!(real("value_si(1|km|)"), 1000.0);
+        assert_eq!(real("value_si(1|h|)"), 3600.0);
+        let metres = number("to_si(1|km|)");
+        assert_eq!((metres.re, metres.unit), (1000.0, unit("m")));
+        assert_eq!(error("value_si([1,2])"), "The 'value_si' function takes on value of type 'Number' but an element of type 'Matrix' was found.");
+    }
+
+    #[test]
+    fn nthroot_takes_principal_roots_and_divides_the_units() {
+        assert_eq!(real("nthroot(27, 3)"), 3.0);
+        let side = number("nthroot(8|m3|, 3)");
+        assert_eq!((side.re, side.unit), (2.0, unit("m")));
+        // d/dx x^(1/3) = x^(1/3) / (3x) = 1/27 at 27
+        assert!((real("sigma(nthroot(27 ± 2.7, 3))") - 0.1).abs() < 1e-12);
+        let root = number("nthroot(-8, 3)");
+        assert!((root.re - 1.0).abs() < 1e-12 && (root.im - 3f64.sqrt()).abs() < 1e-12);
+        assert_eq!(error("nthroot(4|m|, 2)"), "Cannot take the root of index 2 of '4m' because its unit exponents are not divisible by 2.");
+    }
+
+    #[test]
+    fn nthroot_checks_the_index() {
+        assert_eq!(error("nthroot(4, 0)"), "The 'nthroot' function takes a pure integer index between 1 and 127 but '0' was found.");
+        assert_eq!(error("nthroot(4, -2)"), "The 'nthroot' function takes a pure integer index between 1 and 127 but '-2' was found.");
+        assert_eq!(error("nthroot(4, 1.5)"), "The 'nthroot' function takes a pure integer index between 1 and 127 but '1.5' was found.");
+        // larger indices would not fit the unit exponents, instead of being truncated
+        assert_eq!(error("nthroot(4, 4294967298)"), "The 'nthroot' function takes a pure integer index between 1 and 127 but '4294967298' was found.");
+        assert_eq!(error("nthroot(4, \"2\")"), "The 'nthroot' function takes an index of type 'Number' but an element of type 'String' was found.");
+        assert_eq!(real("nthroot(2, 127)"), 2f64.powf(1.0 / 127.0));
+    }
+
+    #[test]
+    fn hypot_combines_values_and_uncertainties() {
+        assert_eq!(real("hypot(3, 4)"), 5.0);
+        let h = number("hypot(3|m|, 4|m|)");
+        assert_eq!((h.re, h.unit), (5.0, unit("m")));
+        // σ² = (3/5)²·0.3² + (4/5)²·0.4²
+        assert!((real("sigma(hypot(3 ± 0.3, 4 ± 0.4))") - (0.0324f64 + 0.1024).sqrt()).abs() < 1e-12);
+        // neither the value nor its uncertainty overflow for huge inputs
+        let (e200, e100) = (format!("1{}", "0".repeat(200)), format!("1{}", "0".repeat(100)));
+        let huge = number(&format!("hypot({e200} ± {e100}, {e200})"));
+        assert!((huge.re / 1e200 - 2f64.sqrt()).abs() < 1e-12 && huge.vre.is_finite());
+        assert_eq!(error("hypot(3|m|, 4|s|)"), "The 'hypot' function operates on quantities with the same units but '3m' and '4s' were found.");
+    }
+
+    #[test]
+    fn isnan_and_isinf_detect_ieee_special_values() {
+        assert!(truth("isnan(0/0)"));
+        assert!(!truth("isnan(1)"));
+        assert!(truth("isinf(1/0)"));
+        assert!(truth("isinf(-1/0)"));
+        assert!(!truth("isinf(0/0)"));
+        assert_eq!(error("isnan(\"a\")"), "The 'isnan' function takes on value of type 'Number' but an element of type 'String' was found.");
+    }
+
+    #[test]
+    fn ieee_special_values_are_displayed() {
+        assert_eq!(display("1/0"), "inf");
+        assert_eq!(display("1.0/0.0"), "inf");
+        assert_eq!(display("-1/0"), "-inf");
+        assert_eq!(display("0/0"), "NaN");
+        assert_eq!(display("{ x = 1/0; x }"), "inf");
+        assert!(matches!(eval("print(1/0)"), RValue::Void));
+        // the uncertainty of a division of exact values is zero, even by zero
+        assert_eq!(number("1/0").vre, 0.0);
+        assert!(truth("1/0 == 1/0"));
+        assert_eq!(display("1/0 + (1 ± 0.1)"), "inf ± 0.1");
+        assert_eq!(display("(1/0)|m|"), "infm");
+        assert_eq!(display("(1/0 + (1 ± 0.1))|m|"), "inf ± 0.1m");
+    }
+
+    #[test]
+    fn if_else_is_an_expression_that_can_be_assigned() {
+        assert_eq!(real("if 1 { 10 } else { 20 }"), 10.0);
+        assert_eq!(real("if 0 { 10 } else { 20 }"), 20.0);
+        assert_eq!(real("{ x = if 1 { 10 } else { 20 }; x }"), 10.0);
+        assert_eq!(real("{ x = if 0 { 1 } else if 1 { 2 } else { 3 }; x }"), 2.0);
+        assert!(matches!(eval("{ x = if 0 { 1 }; x }"), RValue::Void));
+    }
+
+    #[test]
+    fn tuples_are_destructured_into_variables() {
+        assert_eq!(real("{ (a, b) = (1, 2); 10*a + b }"), 12.0);
+        assert_eq!(real("{ (q, r) = divmod(7, 3); 10*q + r }"), 21.0);
+        assert_eq!(matrix("divmod(-7, 3)"), (2, 1, vec![-3.0, 2.0]));
+        assert_eq!(error("(a, b) = (1, 2, 3)"), "Cannot destructure 3 values into 2 variables.");
+        assert_eq!(error("(a, b, c) = divmod(7, 3)"), "Cannot destructure 2 values into 3 variables.");
+    }
+
+    #[test]
+    fn swap_exchanges_two_variables() {
+        assert_eq!(display("{ a = 1; b = 2|m|; swap(a, b); a }"), "2m");
+        assert_eq!(real("{ a = 1; b = 2|m|; swap(a, b); b }"), 1.0);
+        assert_eq!(error("{ a = 1; swap(a, c) }"), "The 'swap' function can't swap 'c' because it is not an existing variable.");
+    }
+
+    #[test]
+    fn comparisons_produce_booleans() {
+        assert!(truth("1 < 2"));
+        assert!(!truth("1|m| > 2|m|"));
+        assert_eq!(string("type(1<2)"), "Bool");
+        assert!(truth("type(1<2) == \"Bool\""));
+        assert_eq!(display("1 < 2"), "true");
+        assert_eq!(real("if 1 < 2 { 5 } else { 6 }"), 5.0);
+        assert_eq!(real("{ i = 0; while i < 3 { i = i + 1 }; i }"), 3.0);
+        assert!(!truth("!(1 < 2)"));
+        assert!(!truth("(1 < 2) and (2 > 3)"));
+        assert_eq!(error("1|m| < 2|s|"), "The binary '<' operator operates on quantities with the same units but 'm' and 's' were found.");
+    }
+
+    #[test]
+    fn booleans_compare_and_coerce_to_numbers() {
+        assert!(truth("(1 < 2) == (2 < 3)"));
+        assert!(truth("(1 < 2) == 1"));
+        assert!(truth("\"a\" == \"a\""));
+        assert!(!truth("\"a\" == \"b\""));
+        assert_eq!(real("2*(1 < 2)"), 2.0);
+        assert!(truth("(1 < 2) or 0"));
+        assert_eq!(error("\"x\" == 1"), "The '==' operator cannot compare a value of type 'String' with one of type 'Number'.");
+    }
+
+    #[test]
+    fn bitwise_operators_act_on_non_negative_integers() {
+        assert_eq!(real("band(12, 10)"), 8.0);
+        assert_eq!(real("bor(12, 10)"), 14.0);
+        assert_eq!(real("bxor(12, 10)"), 6.0);
+        assert_eq!(real("shl(1, 4)"), 16.0);
+        assert_eq!(real("shr(16, 2)"), 4.0);
+        assert_eq!(real("shl(1, 63)"), 2f64.powi(63));
+        assert_eq!(error("band(1.5, 1)"), "The 'band' operator operates on pure, non negative, integer, unitless values but '1.5' was found.");
+        assert_eq!(error("band(-1, 1)"), "The 'band' operator operates on pure, non negative, integer, unitless values but '-1' was found.");
+        assert_eq!(error("bor(1|m|, 1)"), "The 'bor' operator operates on pure, non negative, integer, unitless values but '1m' was found.");
+        assert_eq!(error("shl(1, 64)"), "Overflow happened while shifting 1 to the left by 64.");
+    }
+
+    #[test]
+    fn prod_multiplies_values_units_and_uncertainties() {
+        assert_eq!(real("prod([2, 3, 4])"), 24.0);
+        assert_eq!(real("prod([1, 2; 3, 4])"), 24.0);
+        let area = number("prod([2|m|, 3|m|])");
+        assert_eq!((area.re, area.unit), (6.0, unit("m2")));
+        // relative uncertainties add in quadrature
+        assert!((real("sigma(prod([2 ± 0.2, 3 ± 0.3]))") - 6.0*0.02f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sum_counts_booleans_as_numbers() {
+        assert_eq!(real("sum([1 < 2, 2 < 3, 3 < 1])"), 2.0);
+        assert_eq!(real("prod([1 < 2, 2 < 3])"), 1.0);
+    }
+
+    #[test]
+    fn cumsum_returns_the_running_sums() {
+        assert_eq!(matrix("cumsum([1, 2, 3, 4])"), (1, 4, vec![1.0, 3.0, 6.0, 10.0]));
+        assert_eq!(display("cumsum([1|m|, 2|m|])"), "Matrix 2×1: [1m; 3m]");
+        assert_eq!(error("cumsum([1|m|, 2|s|])"), "The 'cumsum' function operates on quantities with the same units but '1m' and '2s' were found.");
+        assert_eq!(error("cumsum([1, 2; 3, 4])"), "The 'cumsum' function operates on vectors but a matrix 2×2 was found.");
+    }
+
+    #[test]
+    fn diff_returns_the_differences_of_neighbours() {
+        assert_eq!(matrix("diff([1, 3, 6, 10])"), (1, 3, vec![2.0, 3.0, 4.0]));
+        assert_eq!(matrix("diff(cumsum([1, 2, 3]))"), (1, 2, vec![2.0, 3.0]));
+        assert_eq!(display("diff([1|m|, 3|m|])"), "Matrix 1×1: [2m]");
+        assert_eq!(error("diff([1|m|, 3])"), "The 'diff' function operates on quantities with the same units but '1m' and '3' were found.");
+        assert_eq!(error("diff([1])"), "The 'diff' function needs a vector of at least two elements but 1 were found.");
+    }
+
+    #[test]
+    fn writefile_writes_strings_and_values() {
+        let path = temp_path("writefile.txt");
+        assert!(matches!(eval(&format!("writefile(\"{path}\", \"hello\")")), RValue::Void));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+        eval(&format!("writefile(\"{path}\", 3|m|)"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "3m");
+        std::fs::remove_file(&path).unwrap();
+        assert!(error(&format!("writefile(\"{path}/missing/file.txt\", \"a\")")).starts_with(&format!("Unable to write to the file '{path}/missing/file.txt': ")));
+    }
+
+    #[test]
+    fn readfile_returns_the_contents_as_a_string() {
+        let path = temp_path("readfile.txt");
+        eval(&format!("writefile(\"{path}\", \"1,2,3\")"));
+        assert_eq!(string(&format!("readfile(\"{path}\")")), "1,2,3");
+        assert_eq!(display(&format!("split(readfile(\"{path}\"), \",\")")), "Matrix 3×1: [\"1\"; \"2\"; \"3\"]");
+        std::fs::remove_file(&path).unwrap();
+        assert!(error(&format!("readfile(\"{path}\")")).starts_with(&format!("Unable to read the file '{path}': ")));
+    }
+
+    #[test]
+    fn env_expects_a_variable_name() {
+        assert_eq!(error("env(1)"), "The 'env' function expects a value of type 'String' but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn now_is_monotonic_and_measures_sleep() {
+        assert!(truth("{ a = now(); b = now(); b >= a }"));
+        assert_eq!(number("now()").unit, unit("s"));
+        assert!(truth("{ a = now(); sleep(0.02|s|); now() - a >= 0.02|s| }"));
+        assert!(matches!(eval("sleep(0)"), RValue::Void));
+        assert_eq!(error("sleep(1|m|)"), "The 'sleep' function takes a non negative duration in seconds but '1m' was found.");
+        assert_eq!(error("sleep(-1)"), "The 'sleep' function takes a non negative duration in seconds but '-1' was found.");
+    }
+
+    #[test]
+    fn error_concatenates_its_arguments() {
+        assert_eq!(error("error(\"bad value: \", 3|m|, \"!\")"), "bad value: 3m!");
+        assert_eq!(error("error(\"plain\")"), "plain");
+        assert_eq!(error("error(2)"), "2");
+        assert_eq!(error("error()"), "explicit panic");
+    }
+
+    #[test]
+    fn assert_eq_reports_both_values() {
+        assert_eq!(error("assert_eq(2+2, 5)"), "Assertion failed, the values are not equal\n  left: 4\n right: 5");
+        assert_eq!(error("assert_eq(1|m|, 1|s|)"), "Assertion failed, the values are not equal\n  left: 1m\n right: 1s");
+        // the comparison is made in SI units
+        assert!(matches!(eval("assert_eq(1|m|, 100|cm|)"), RValue::Void));
+        assert!(matches!(eval("assert_eq(\"a\", \"a\")"), RValue::Void));
+        assert_eq!(error("assert(0, \"custom\")"), "custom");
+    }
+
+    #[test]
+    fn debug_passes_the_value_through() {
+        let n = number("debug(3 pm 1) + 1");
+        assert_eq!((n.re, n.sigma_re()), (4.0, 1.0));
+        assert_eq!(display("debug(\"text\")"), "text");
+    }
+
+    #[test]
+    fn count_counts_the_nonzero_cells() {
+        assert_eq!(real("count([1, 0, 2, 0, 3])"), 3.0);
+        assert_eq!(real("count([0, 0])"), 0.0);
+        assert_eq!(real("count([0 + 1i, 0])"), 1.0);
+        assert_eq!(error("count(3)"), "The 'count' function operates on matrices but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn any_and_all_reduce_matrices_of_conditions() {
+        assert!(truth("all([1, 1, 1])"));
+        assert!(!truth("all([1, 0, 1])"));
+        assert!(truth("any([0, 0, 1])"));
+        assert!(!truth("any([0, 0])"));
+        assert!(truth("any([1 < 2, 2 < 1])"));
+        assert!(!truth("all([1 < 2, 2 < 1])"));
+        assert_eq!(error("all(1)"), "The 'all' function operates on matrices but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn round_to_rounds_to_multiples_of_the_step() {
+        let length = number("round_to(7.3|m|, 0.5|m|)");
+        assert_eq!((length.re, length.unit), (7.5, unit("m")));
+        assert_eq!(real("round_to(7.3, 2)"), 8.0);
+        assert_eq!(real("round_to(-7.3, 0.25)"), -7.25);
+        // decimal steps do not leave binary rounding errors
+        assert_eq!(number("round_to(7.3|m|, 10|cm|)").re, 7.3);
+        assert_eq!(real("round_to(1.23456, 0.01)"), 1.23);
+        assert_eq!(number("round_to(7.3 pm 1, 1)").vre, 0.0);
+        assert_eq!(error("round_to(7.3, 0)"), "The 'round_to' function needs a real non zero step but '0' was found.");
+        assert_eq!(error("round_to(7.3|m|, 1|s|)"), "The 'round_to' function operates on quantities with the same units but '7.3m' and '1s' were found.");
+    }
+
+    #[test]
+    fn vectors_are_indexed_from_one() {
+        assert_eq!(real("{ v = [1, 2, 3]; v[2] }"), 2.0);
+        assert_eq!(real("{ v = [1; 2; 3]; v[2] }"), 2.0);
+        let message = "Matrix indices are 1-based, so only pure, unitless, integer, non zero values are allowed when indexing a matrix (negative values count from the end)";
+        assert_eq!(error("{ v = [1, 2, 3]; v[0] }"), format!("{message} but '0' was found."));
+        assert_eq!(error("{ v = [1, 2, 3]; v[1.5] }"), format!("{message} but '1.5' was found."));
+        assert_eq!(error("{ m = [1, 2; 3, 4]; m[0, 1] }"), format!("{message} but '0' was found."));
+    }
+
+    #[test]
+    fn a_single_index_reads_matrices_in_row_major_order() {
+        for k in 1..=4 {
+            assert_eq!(real(&format!("{{ m = [1, 2; 3, 4]; m[{k}] }}")), k as f64);
+        }
+        assert_eq!(real("{ m = [1, 2; 3, 4]; m[2, 1] }"), 3.0);
+        assert_eq!(error("{ m = [1, 2; 3, 4]; m[5] }"), "Index must not exceed Matrix bounds. Matrix 'm' is '2×2' but '5' was found.");
+    }
+
+    #[test]
+    fn assignments_return_the_assigned_value() {
+        assert_eq!(real("{ a = b = 5; 10*a + b }"), 55.0);
+        assert_eq!(real("{ x = (y = 3) + 1; 10*x + y }"), 43.0);
+        assert_eq!(real("{ a = 2 }"), 2.0);
+    }
+
+    #[test]
+    fn range_loops_iterate_lazily() {
+        assert_eq!(matrix("for i in range(1, 4) { i*i }"), (1, 3, vec![1.0, 4.0, 9.0]));
+        assert_eq!(matrix("for i in range(0, 1, 0.25) { i }"), (1, 4, vec![0.0, 0.25, 0.5, 0.75]));
+        assert_eq!(real("{ s = 0; for i in range(1, 10^4) { s = s + i }; s }"), 49995000.0);
+        // the values are not allocated ahead of the loop
+        assert_eq!(error("for i in range(1, 10^12) { if i > 2 { error(\"stopped at \", i) }; i }"), "stopped at 3");
+        assert_eq!(error("for i in range(1, 10^22) { i }"), "The 'range' function cannot produce 10000000000000000000000 values, from 1 to 10000000000000000000000 with a step of 1.");
+        assert_eq!(error("for i in range(1, 4, 0) { i }"), "The 'range' function needs a non zero step.");
+    }
+
+    #[test]
+    fn zipmap_applies_a_function_to_pairs_of_cells() {
+        assert_eq!(matrix("zipmap([1, 2], [3, 4], \"+\")"), (2, 1, vec![4.0, 6.0]));
+        assert_eq!(matrix("zipmap([1, 2; 3, 4], [5, 1; 2, 6], \"max\")"), (2, 2, vec![5.0, 2.0, 3.0, 6.0]));
+        assert_eq!(display("zipmap([1|m|, 2|m|], [3|m|, 4|m|], \"+\")"), "Matrix 1×2: [4m, 6m]");
+        assert_eq!(display("zipmap([1, 5], [3, 4], \"<\")"), "Matrix 1×2: [true, false]");
+        assert_eq!(error("zipmap([1, 2], [3, 4, 5], \"+\")"), "The 'zipmap' function operates on matrices with the same shape but a '1×2' and a '1×3' matrix were found.");
+        assert_eq!(error("zipmap([1, 2], [3, 4], \"foo\")"), "Unknown function called 'foo'");
+        // the values are bound outside of the variables of the program
+        assert_eq!(real("{ arg0 = 7; zipmap([1], [2], \"+\"); arg0 }"), 7.0);
+    }
+
+    #[test]
+    fn reduce_folds_left_in_row_major_order() {
+        assert_eq!(real("reduce([1, 2, 3, 4], 0, \"+\")"), 10.0);
+        assert_eq!(real("reduce([1, 2; 3, 4], 1, \"*\")"), 24.0);
+        // ((10 - 1) - 2) - 3
+        assert_eq!(real("reduce([1, 2, 3], 10, \"-\")"), 4.0);
+        assert_eq!(real("reduce([4, 3], 0, \"hypot\")"), 5.0);
+        let total = number("reduce([1|m|, 2|m|], 0|m|, \"+\")");
+        assert_eq!((total.re, total.unit), (3.0, unit("m")));
+    }
+
+    #[test]
+    fn diag_extracts_and_builds_diagonals() {
+        assert_eq!(matrix("diag([1, 2; 3, 4])"), (1, 2, vec![1.0, 4.0]));
+        assert_eq!(matrix("diag([1, 2, 3])"), (3, 3, vec![1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0]));
+        assert_eq!(matrix("diag([1; 2; 3])"), matrix("diag([1, 2, 3])"));
+        assert_eq!(matrix("diag(diag([1, 2, 3]))"), (1, 3, vec![1.0, 2.0, 3.0]));
+        assert_eq!(display("diag([1|m|, 2|m|])"), "Matrix 2×2: [1m, 0m; 0m, 2m]");
+        assert_eq!(error("diag([1, 2, 3; 4, 5, 6])"), "The 'diag' function operates on square matrices or vectors but a '2×3' matrix was found.");
+    }
+
+    #[test]
+    fn trace_sums_the_diagonal() {
+        assert_eq!(real("trace([1, 2; 3, 4])"), 5.0);
+        assert_eq!(display("trace([1|m|, 2|s|; 3|s|, 4|m|])"), "5m");
+        let t = number("trace([1 pm 0.3, 0; 0, 2 pm 0.4])");
+        assert_eq!(t.re, 3.0);
+        assert!((t.sigma_re() - 0.5).abs() < 1e-12);
+        assert_eq!(error("trace([1, 2, 3; 4, 5, 6])"), "The 'trace' function operates on square matrices but a '2×3' matrix was found.");
+        assert_eq!(error("trace([1|m|, 2|m|; 3|m|, 4|s|])"), "The 'trace' function operates on quantities with the same units but '1m' and '4s' were found.");
+    }
+
+    #[test]
+    fn rank_counts_the_independent_rows() {
+        assert_eq!(real("rank([1, 2, 3; 0, 1, 4; 5, 6, 0])"), 3.0);
+        assert_eq!(real("rank([1, 2; 2, 4])"), 1.0);
+        assert_eq!(real("rank([0, 0; 0, 0])"), 0.0);
+        assert_eq!(real("rank([1, 2, 3; 4, 5, 6])"), 2.0);
+        assert_eq!(real("rank([1|m|, 2|m|; 3|m|, 4|m|])"), 2.0);
+    }
+
+    #[test]
+    fn solve_solves_linear_systems() {
+        // 2x + y = 3, x + 3y = 5
+        assert_eq!(matrix("solve([2, 1; 1, 3], [3, 5])"), (1, 2, vec![0.8, 1.4]));
+        assert_eq!(matrix("solve([2, 1; 1, 3], [3; 5])"), (1, 2, vec![0.8, 1.4]));
+        assert_eq!(display("solve([2|m|, 0; 0, 2|m|], [4|N|, 2|N|])"), "Matrix 2×1: [2|N.m⁻¹|; 1|N.m⁻¹|]");
+        assert_eq!(error("solve([1, 2; 2, 4], [1, 2])"), "The 'solve' function cannot solve a system whose matrix is singular.");
+        assert_eq!(error("solve([1, 2; 3, 4], [1, 2, 3])"), "The 'solve' function needs a vector with 2 elements for a '2×2' matrix but 3 elements were found.");
+        assert_eq!(error("solve([1, 2, 3; 4, 5, 6], [1, 2])"), "The 'solve' function needs a square matrix but a '2×3' matrix was found.");
+    }
+
+    #[test]
+    fn print_needs_at_least_one_parameter() {
+        assert_eq!(error("print()"), "The 'print' function takes one or more parameters but no parameters were found.");
+    }
+
+    #[test]
+    fn interpolation_accepts_a_precision() {
+        assert_eq!(string("{ x = 3.14159; \"v={x:.3}\" }"), "v=3.142");
+        assert_eq!(string("{ x = 1234|m|; \"{x|km|:.2}\" }"), "1.23km");
+        assert_eq!(string("{ x = 1234|m|; \"{x|km|}\" }"), "1.234km");
+        assert_eq!(string("{ x = 2 pm 0.1234; \"{x:.2}\" }"), "2.00 ± 0.12");
+        assert_eq!(error("{ x = 1; \"{x:.}\" }"), "Unknown format specifier '.' inside string, only precisions such as '.3' are allowed: '{x:.}'");
+        assert_eq!(error("{ x = \"a\"; \"{x:.2}\" }"), "Format specifiers inside strings can only be applied to numbers but 'x' is of type 'String'.");
+    }
+
+    #[test]
+    fn interpolation_evaluates_expressions() {
+        assert_eq!(string("\"{2*3}\""), "6");
+        assert_eq!(string("{ a = 1; b = 2; \"sum is {a + b} and {a}\" }"), "sum is 3 and 1");
+        // a final unit right after a number belongs to the number, otherwise it is the unit to display
+        assert_eq!(string("{ x = 1|m|; \"{x + 1|m|}\" }"), "2m");
+        assert_eq!(string("{ x = 1|m|; \"{x + 1|m| |cm|}\" }"), "200cm");
+        assert_eq!(string("{ x = 1|m|; \"{(x + 1|m|)|cm|:.1}\" }"), "200.0cm");
+        assert_eq!(string("{ x2 = 3|m|; \"{x2|cm|}\" }"), "300cm");
+        assert!(error("\"{2*}\"").starts_with("The parsing couldn't finish."));
+        assert_eq!(error("\"{nope}\""), "Unable to give value to string block due to unknown variable: 'nope'");
+        assert_eq!(error("\"{|m|}\""), "String block contains an empty '{}': '{|m|}'");
+        assert_eq!(error("\"{x|m}\""), "Opening '|' inside string is missing a corresponding '|': '{x|m}'");
+        assert_eq!(error("\"{ {x} }\""), "String block cannot contain nested brackets: '{ {x} }'");
+    }
+
+    #[test]
+    fn histogram_returns_bin_centers_and_counts() {
+        // one row for each bin: its center and its count
+        assert_eq!(matrix("histogram([1, 2, 3, 4, 10], 2)"), (2, 2, vec![3.25, 4.0, 7.75, 1.0]));
+        // values outside of the range are not counted, the last bin includes its right edge
+        assert_eq!(matrix("histogram([1, 2, 3, 4], 2, [0, 8])"), (2, 2, vec![2.0, 3.0, 6.0, 1.0]));
+        assert_eq!(matrix("histogram([1, 20, 8], 2, [0, 8])"), (2, 2, vec![2.0, 1.0, 6.0, 1.0]));
+        assert_eq!(display("histogram([1|m|, 3|m|], 2)"), "Matrix 2×2: [1.5m, 1; 2.5m, 1]");
+        assert_eq!(error("histogram([1, 2], 0)"), "The 'histogram' function needs at least one bin.");
+        assert_eq!(error("histogram([1, 2], 2, [5, 1])"), "The 'histogram' function expects its range as a vector [low, high] with low <= high.");
+        assert_eq!(error("histogram([1|m|, 2|s|], 2)"), "The 'histogram' function operates on quantities with the same units but '1m' and '2s' were found.");
+    }
+
+    #[test]
+    fn median_takes_the_middle_value() {
+        assert_eq!(real("median([3, 1, 2])"), 2.0);
+        assert_eq!(real("median([4, 1, 3, 2])"), 2.5);
+        assert_eq!(real("median([1; 5; 3])"), 3.0);
+        assert_eq!(display("median([1|m|, 3|m|])"), "2m");
+        assert_eq!(error("median(range(1, 1))"), "The 'median' function operates on non empty vectors.");
+        assert_eq!(error("median([1|m|, 2|s|])"), "The 'median' function operates on quantities with the same units but '1m' and '2s' were found.");
+        assert_eq!(error("median([1, 2; 3, 4])"), "The 'median' function operates on vectors but a matrix 2×2 was found.");
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        assert_eq!(real("percentile([5, 1, 4, 2, 3], 0)"), 1.0);
+        assert_eq!(real("percentile([5, 1, 4, 2, 3], 50)"), 3.0);
+        assert_eq!(real("percentile([5, 1, 4, 2, 3], 100)"), 5.0);
+        assert_eq!(real("percentile([1, 2, 3, 4], 25)"), 1.75);
+        assert_eq!(display("percentile([1|m|, 3|m|], 50)"), "2m");
+        let message = "The 'percentile' function expects a pure real number between 0 and 100";
+        assert_eq!(error("percentile([1, 2], 101)"), format!("{message} but '101' was found."));
+        assert_eq!(error("percentile([1, 2], -1)"), format!("{message} but '-1' was found."));
+        assert_eq!(error("percentile([1, 2], 50|m|)"), format!("{message} but '50m' was found."));
+        assert_eq!(error("percentile(range(1, 1), 50)"), "The 'percentile' function operates on non empty vectors.");
+    }
+
+    #[test]
+    fn floor_ceil_and_round_map_over_matrices() {
+        assert_eq!(matrix("floor([1.2, 2.8; 3.5, 4.9])"), (2, 2, vec![1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(matrix("ceil([1.2, -2.8])"), (2, 1, vec![2.0, -2.0]));
+        assert_eq!(matrix("round([1.5, 2.4])"), (2, 1, vec![2.0, 2.0]));
+        assert_eq!(display("floor([1.5|m|, 2|s|])"), "Matrix 1×2: [1m, 2s]");
+        assert_eq!(error("floor([\"a\"])"), "The 'floor' function operates on matrices of numbers but an element of type 'String' was found.");
+    }
+
+    #[test]
+    fn abs_maps_over_matrices() {
+        assert_eq!(matrix("abs([-1, 2; -3, 4])"), (2, 2, vec![1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(display("abs([3 + 4i, -1|m|])"), "Matrix 1×2: [5, 1m]");
+    }
+
+    #[test]
+    fn unary_functions_map_over_matrices_keeping_the_shape() {
+        let (w, h, cells) = matrix("sin([0, 1; 2, 3])");
+        assert_eq!((w, h), (2, 2));
+        assert_eq!(cells, vec![0f64.sin(), 1f64.sin(), 2f64.sin(), 3f64.sin()]);
+        assert_eq!(matrix("sin([0; 1; 2])"), (1, 3, vec![0f64.sin(), 1f64.sin(), 2f64.sin()]));
+        assert_eq!(matrix("exp([0, 1])"), (2, 1, vec![1.0, 1f64.exp()]));
+        assert_eq!(display("sqrt([4, -4])"), "Matrix 1×2: [2, 0 + 2i]");
+        assert_eq!(error("sin([0, 1|m|])"), "The 'sin' function operates on unitless quantities but '1m' was found.");
+    }
+
+    #[test]
+    fn while_loops_run_up_to_the_iteration_limit() {
+        let mut evaluator = evaluator("{ i = 0; while i < 100 { i = i + 1 }; i }");
+        evaluator.max_while_iterations = 100;
+        match evaluator.eval() {
+            RValue::Number(n) => assert_eq!(n, 100.0),
+            other => panic!("unexpected result: {other}"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "while loop exceeded 100 iterations, possible infinite loop")]
+    fn while_loops_beyond_the_iteration_limit_are_stopped() {
+        let mut evaluator = evaluator("while 1 {}");
+        evaluator.max_while_iterations = 100;
+        evaluator.eval();
+    }
+
+    #[test]
+    fn deep_nesting_stops_at_the_depth_limit() {
+        let message = format!("maximum recursion depth exceeded: expressions can be nested at most {DEFAULT_MAX_DEPTH} times");
+        // the number under 999 minus signs is the 1000th nested expression
+        assert_eq!(real(&format!("{}1", "-".repeat(999))), -1.0);
+        assert_eq!(error(&format!("{}1", "-".repeat(1000))), message);
+        assert_eq!(error(&format!("{}1{}", "1 + (".repeat(1000), ")".repeat(1000))), message);
+        // far beyond the limit the error is still clean
+        assert_eq!(error(&format!("{}1", "-".repeat(2500))), message);
+    }
+
+    #[test]
+    fn long_chains_of_operators_are_not_nested() {
+        assert_eq!(real(&format!("{}1", "1 + ".repeat(10000))), 10001.0);
+        assert_eq!(real(&format!("{}1", "2 - 1 + ".repeat(5000))), 5001.0);
+        assert!(truth(&format!("{}1 == 1", "1 * ".repeat(5000))));
+    }
+
+    #[test]
+    fn the_depth_limit_is_configurable() {
+        let mut evaluator = evaluator(&format!("{}1", "-".repeat(20)));
+        evaluator.max_depth = 10;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| evaluator.eval()));
+        let message = EvalError::from_panic(result.unwrap_err()).message;
+        assert_eq!(message, "maximum recursion depth exceeded: expressions can be nested at most 10 times");
+        // the evaluator can run again with a larger limit
+        evaluator.max_depth = 100;
+        match evaluator.eval() {
+            RValue::Number(n) => assert_eq!(n, 1.0),
+            other => panic!("unexpected result: {other}"),
+        }
+    }
+
+    #[test]
+    fn interpolation_extracts_the_whole_unit() {
+        assert_eq!(string("{ x = 2|m|; \"{x|m|}\" }"), "2m");
+        assert_eq!(string("{ x = 2|m/s|; \"{x|km/s|}\" }"), "0.002km/s");
+        assert_eq!(string("{ x = 2|m|; \"{x| m |}\" }"), "2m");
+        assert_eq!(string("{ x = 2|m|; \"[{x|cm|}]\" }"), "[200cm]");
+    }
+
+    #[test]
+    fn unary_minus_negates_every_cell() {
+        assert_eq!(matrix("-[1, 2]"), (2, 1, vec![-1.0, -2.0]));
+        assert_eq!(matrix("-[1, 2; 3, 4]"), (2, 2, vec![-1.0, -2.0, -3.0, -4.0]));
+        match eval("-[1 + 2i, 4|m|]") {
+            RValue::Matrix(2, 1, cells) => match &cells[..] {
+                [RValue::Number(a), RValue::Number(b)] => {
+                    assert_eq!((a.re, a.im), (-1.0, -2.0));
+                    assert_eq!((b.re, b.unit.clone()), (-4.0, unit("m")));
+                }
+                other => panic!("unexpected cells: {other:?}"),
+            },
+            other => panic!("unexpected result: {other}"),
+        }
+    }
+
+    #[test]
+    fn kron_scales_a_copy_of_b_by_each_cell_of_a() {
+        let (w, h, cells) = matrix("kron([1, 2; 3, 4], [0, 5; 6, 7])");
+        assert_eq!((w, h), (4, 4));
+        assert_eq!(cells, vec![
+            0.0, 5.0, 0.0, 10.0,
+            6.0, 7.0, 12.0, 14.0,
+            0.0, 15.0, 0.0, 20.0,
+            18.0, 21.0, 24.0, 28.0,
+        ]);
+        assert_eq!(display("kron([1|m|, 2|m|], [1|s|; 2|s|])"), "Matrix 2×2: [1|m.s|, 2|m.s|; 2|m.s|, 4|m.s|]");
+        assert_eq!(error("kron(1, [1])"), "The 'kron' function operates on matrices but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn outer_multiplies_every_pair_of_cells() {
+        assert_eq!(matrix("outer([1, 2], [3, 4])"), (2, 2, vec![3.0, 4.0, 6.0, 8.0]));
+        assert_eq!(matrix("outer([1; 2], [3; 4])"), (2, 2, vec![3.0, 4.0, 6.0, 8.0]));
+        assert_eq!(display("outer([1|m|, 2|m|], [3|s|])"), "Matrix 2×1: [3|m.s|; 6|m.s|]");
+        assert_eq!(error("outer([1, 2; 3, 4], [1])"), "The 'outer' function operates on vectors but a matrix 2×2 was found.");
+    }
+
+    #[test]
+    fn tile_repeats_a_matrix() {
+        assert_eq!(matrix("tile([1, 2], 2, 2)"), (4, 2, vec![1.0, 2.0, 1.0, 2.0, 1.0, 2.0, 1.0, 2.0]));
+        assert_eq!(matrix("tile([1; 2], 1, 3)"), (3, 2, vec![1.0, 1.0, 1.0, 2.0, 2.0, 2.0]));
+        assert_eq!(matrix("tile([1, 2; 3, 4], 2, 1)"), (2, 4, vec![1.0, 2.0, 3.0, 4.0, 1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(error("tile([1, 2], 0, 1)"), "The 'tile' function needs positive repetition counts but '0' and '1' were found.");
+        assert_eq!(error("tile([1, 2], 1.5, 1)"), "The 'tile' function expects a pure, non negative, integer count but '1.5' was found.");
+    }
+
+    #[test]
+    fn flatten_lists_the_cells_in_row_major_order() {
+        assert_eq!(matrix("flatten([1, 2, 3; 4, 5, 6])"), (1, 6, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+        assert_eq!(matrix("flatten([1; 2])"), (1, 2, vec![1.0, 2.0]));
+        // the same order of linear indexing
+        for k in 1..=6 {
+            assert!(truth(&format!("{{ m = [1, 2, 3; 4, 5, 6]; f = flatten(m); f[{k}] == m[{k}] }}")));
+        }
+        assert_eq!(error("flatten(3)"), "The 'flatten' function operates on matrices but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn apply_unit_attaches_a_unit_known_at_runtime() {
+        assert!(truth("apply_unit(5, \"m/s\") == 5|m/s|"));
+        assert_eq!(display("apply_unit(5, \"m/s\")"), "5|m.s⁻¹|");
+        assert_eq!(display("apply_unit(1, \"km\")"), "1000m");
+        // shifted units are converted as unit blocks are
+        assert_eq!(display("apply_unit(20, \"°C\")"), "293.15K");
+        assert_eq!(display("20|°C|"), "293.15K");
+        assert_eq!(display("55|°C|"), "328.15K");
+        assert_eq!(string("{ x = apply_unit(20, \"°C\"); \"{x|°C|}\" }"), "20°C");
+        assert_eq!(error("apply_unit(5|m|, \"s\")"), "Applying units is allowed only on unitless values but '5m' was found");
+        assert_eq!(error("apply_unit(5, \"xyz\")"), "Unknown unit expression 'xyz' due to unknown unit 'xyz'");
+    }
+
+    #[test]
+    fn same_unit_and_dimensionless_compare_units() {
+        assert!(truth("same_unit(1|m|, 2|m|)"));
+        assert!(truth("same_unit(1|km|, 2|m|)"));
+        assert!(!truth("same_unit(1|m|, 2|s|)"));
+        assert!(truth("same_unit(1, 2)"));
+        assert!(truth("dimensionless(3)"));
+        assert!(truth("dimensionless(1|m/m|)"));
+        assert!(!truth("dimensionless(3|m|)"));
+        assert_eq!(error("same_unit(\"a\", 1)"), "The 'same_unit' function takes two values of type 'Number' but an element of type 'String' was found as first parameter.");
+    }
+
+    #[test]
+    fn ratios_can_follow_numbers() {
+        assert_eq!(real("5‰"), 0.005);
+        assert_eq!(real("250|ppm|"), 0.00025);
+        assert_eq!(real("2ppb"), 2e-9);
+        assert_eq!(real("5%"), 0.05);
+    }
+
+    #[test]
+    fn argmax_and_argmin_give_the_linear_index() {
+        assert!(truth("argmax([3, 1, 4, 1, 5])==5"));
+        assert!(truth("argmin([3, 1, 4])==2"));
+        assert_eq!(real("argmin([3, 1; 0, 4])"), 3.0);
+        assert_eq!(real("argmax([1|m|, 30|cm|])"), 1.0);
+        // the first of equal cells
+        assert_eq!(real("argmax([1, 1, 1])"), 1.0);
+        assert_eq!(error("argmax([1|m|, 2|s|])"), "The 'argmax' function operates on quantities with the same units but '1m' and '2s' were found.");
+        assert_eq!(error("argmin(range(1, 1))"), "The 'argmin' function operates on non empty matrices.");
+        assert_eq!(error("argmax(3)"), "The 'argmax' function operates on matrices but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn reset_forgets_every_variable() {
+        let mut evaluator = evaluator("x + 1");
+        evaluator.set_var(String::from("x"), RValue::Number(Quantity::real(2.0)));
+        match evaluator.eval() {
+            RValue::Number(n) => assert_eq!(n, 3.0),
+            other => panic!("unexpected result: {other}"),
+        }
+        evaluator.reset();
+        assert!(evaluator.get_var("x").is_none());
+        // the same error of a fresh state
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| evaluator.eval()));
+        let message = EvalError::from_panic(result.unwrap_err()).message;
+        assert!(message.starts_with("Unable to give value to:"), "unexpected error: {message}");
+        assert_eq!(message, error("x + 1"));
+    }
+
+    #[test]
+    fn vars_lists_the_defined_variables() {
+        assert_eq!(display("{ a = 1; b = 2; vars() }"), "Matrix 2×1: [\"a\"; \"b\"]");
+        assert!(truth("{ a = 1; b = 2; contains(vars(), \"a\") and contains(vars(), \"b\") }"));
+        assert_eq!(matrix("vars()"), (1, 0, vec![]));
+        assert_eq!(error("vars(1)"), "The 'vars' function takes no parameters, but 1 parameters were found.");
+    }
+
+    #[test]
+    fn undef_removes_a_variable() {
+        assert!(error("{ x = 1; undef(x); x }").starts_with("Unable to give value to:"));
+        assert_eq!(display("{ x = 1; y = 2; undef(x); vars() }"), "Matrix 1×1: [\"y\"]");
+        assert_eq!(display("{ x = 1; type(undef(x)) }"), "Void");
+        // the variable can be defined again
+        assert_eq!(real("{ x = 1; undef(x); x = 2; x }"), 2.0);
+        assert_eq!(error("undef(y)"), "The 'undef' function can't remove 'y' because it is not an existing variable.");
+        assert!(error("undef(3)").starts_with("The 'undef' function takes a variable name but "));
+        assert_eq!(error("undef()"), "The 'undef' function takes one parameter, but 0 parameters were found.");
+    }
+
+    #[test]
+    fn unary_minus_binds_looser_than_power() {
+        assert!(truth("-2^2 == -4"));
+        assert!(truth("(-2)^2 == 4"));
+        assert_eq!(real("{ x = 3; -x^2 }"), -9.0);
+        assert_eq!(real("-(2)^2"), -4.0);
+        assert_eq!(real("2*-3^2"), -18.0);
+        assert_eq!(real("-2^2 + 1"), -3.0);
+        // a negative exponent is still allowed
+        assert_eq!(real("2^-2"), 0.25);
+        assert!(truth("!0"));
+    }
+
+    #[test]
+    fn implicit_multiplication_keeps_unit_decorators() {
+        assert_eq!(display("3m"), "3m");
+        assert_eq!(real("{ alpha = 2; 3 alpha }"), 6.0);
+        assert_eq!(real("{ alpha = 2; 3alpha }"), 6.0);
+        // a unit decorator wins over a variable with the same name
+        assert_eq!(display("{ m = 2; 3m }"), "3m");
+        assert_eq!(real("{ m = 2; 3 m }"), 6.0);
+        assert_eq!(real("2(1+1)"), 4.0);
+        assert_eq!(real("{ x = 2; (1+1)x }"), 4.0);
+        assert_eq!(real("3e5"), 3e5);
+        assert_eq!(real("{ e = 2; 3e }"), 6.0);
+        // i and j are the imaginary unit
+        assert_eq!(display("2i"), "0 + 2i");
+    }
+
+    #[test]
+    fn misspelled_units_are_not_variables() {
+        assert!(error("3mm2x").contains("Unknown unit expression 'mm2x'"));
+        assert!(error("3alpha").contains("Unknown unit expression 'alpha'"));
+        assert!(error("{ x = 2; 3mm2x }").contains("Unknown unit expression 'mm2x'"));
+}
+
+
+    #[test]
+    fn arg_has_two_conventions() {
+        use std::f64::consts::PI;
+        assert_eq!(real("arg(-1)"), PI);
+        assert_eq!(real("arg_positive(-1)"), PI);
+        assert_eq!(real("arg(0-1i)"), -PI / 2.0);
+        assert_eq!(real("arg_positive(0-1i)"), 3.0 * PI / 2.0);
+        assert_eq!(display("sqrt(-1)"), "0 + 1i");
+        assert_eq!(display("sqrt(-4)"), "0 + 2i");
+        assert_eq!(display("(-1)^0.5"), "0 + 1i");
+    }
+
+    #[test]
+    fn arg_propagates_both_components() {
+        // σθ² = (b²σa² + a²σb²) / (a² + b²)², the two terms cancelled out before
+        let sigma = real("sigma(arg((3 ± 0.3) + (4 ± 0.4)*1i))");
+        assert!((sigma - 2.88f64.sqrt() / 25.0).abs() < 1e-12);
+        assert_eq!(real("sigma(arg_positive((3 ± 0.3) + (4 ± 0.4)*1i))"), sigma);
+        assert_eq!(real("sigma(arg(-1 ± 0.1))"), 0.0);
+}
+
+
+    #[test]
+    fn round_uncertainty_rounds_value_and_sigma() {
+        assert!(truth("round_uncertainty(12.34 pm 0.46) == 12.3 pm 0.5"));
+        assert!(truth("round_uncertainty(12.34 pm 0.46, 2) == 12.34 pm 0.46"));
+        assert!(truth("round_uncertainty(1.25|m| pm 0.13|m|) == 1.3|m| pm 0.1|m|"));
+        assert_eq!(real("round_uncertainty(5.123)"), 5.123);
+        assert_eq!(error("round_uncertainty(1 pm 0.1, 0)"), "The 'round_uncertainty' function keeps between 1 and 15 significant figures but 0 were asked.");
+        assert_eq!(error("round_uncertainty(\"a\")"), "The 'round_uncertainty' function takes a value of type 'Number' but an element of type 'String' was found.");
+    }
+
+    #[test]
+    fn measure_builds_a_value_with_uncertainty() {
+        assert!(truth("measure(10, 1) == 10 pm 1"));
+        assert_eq!(display("measure(10, 1)"), display("10 pm 1"));
+        assert_eq!(display("measure(2, 1)"), "2 ± 1");
+        assert_eq!(real("sigma(measure(10, 1))"), 1.0);
+        assert_eq!(display("measure(2|m|, 10|cm|)"), "2.0 ± 0.1m");
+        assert_eq!(error("measure(2|m|, 1|s|)"), "The 'measure' function operates on a value and an uncertainty with the same units but '2m' and '1s' were found.");
+        assert_eq!(error("measure(1)"), "The 'measure' function takes two parameters, but 1 parameters were found.");
+    }
+
+    #[test]
+    fn covariance_and_correlation_of_two_vectors() {
+        // means 2.5 and 2.5, the products of the deviations sum to 3 over 3 degrees of freedom
+        assert_eq!(real("covariance([1, 2, 3, 4], [2, 1, 4, 3])"), 1.0);
+        // both variances are 5/3
+        assert!((real("correlation([1, 2, 3, 4], [2, 1, 4, 3])") - 0.6).abs() < 1e-12);
+        assert_eq!(real("correlation([1, 2, 3], [2, 4, 6])"), 1.0);
+        assert_eq!(real("correlation([1, 2, 3], [3, 2, 1])"), -1.0);
+        assert_eq!(display("covariance([1|m|, 2|m|, 3|m|], [1|s|, 2|s|, 4|s|])"), "1.5|m.s|");
+        assert_eq!(error("covariance([1, 2], [1, 2, 3])"), "The 'covariance' function operates on vectors with the same length but lengths 2 and 3 were found.");
+        assert_eq!(error("correlation([1, 1, 1], [1, 2, 3])"), "The 'correlation' function is undefined for data with zero variance.");
+        assert_eq!(error("covariance([1], [1])"), "The 'covariance' function needs at least two data points.");
+    }
+
+    #[test]
+    fn polyval_evaluates_from_the_highest_degree() {
+        assert!(truth("polyval([1, 0, -1], 2)==3"));
+        assert_eq!(real("polyval([1; 0; -1], 2)"), 3.0);
+        assert_eq!(real("polyval([5], 100)"), 5.0);
+        // units and uncertainties propagate
+        assert_eq!(display("polyval([2|m/s|, 1|m|], 3|s|)"), "7m");
+        assert!(truth("polyval([1, 1], 2 pm 0.1) == 3 pm 0.1"));
+        assert_eq!(error("polyval([1, 2; 3, 4], 1)"), "The 'polyval' function operates on vectors but a matrix 2×2 was found.");
+        assert_eq!(error("polyval(range(1, 1), 2)"), "The 'polyval' function needs at least one coefficient.");
+    }
+
+    #[test]
+    fn roots_of_linear_and_quadratic_polynomials() {
+        assert_eq!(matrix("roots([1, 0, -4])"), (1, 2, vec![2.0, -2.0]));
+        assert_eq!(display("roots([1, 0, 1])"), "Matrix 2×1: [0 + 1i; 0 + -1i]");
+        assert_eq!(matrix("roots([1, -2, 1])"), (1, 2, vec![1.0, 1.0]));
+        assert_eq!(matrix("roots([2, -4])"), (1, 1, vec![2.0]));
+        assert_eq!(display("roots([1|m|, -2|m2|])"), "Matrix 1×1: [2m]");
+        assert_eq!(error("roots([1, 2, 3, 4])"), "The 'roots' function only supports polynomials of degree 1 or 2 but 4 coefficients were found.");
+        assert_eq!(error("roots([0, 1, 2])"), "The 'roots' function needs a non zero leading coefficient.");
+    }
+
+    #[test]
+    fn variables_are_listed_in_a_stable_order() {
+        let code = "{ zeta = 1; alpha = 2; mid = 3; b = 4; a = 5; vars() }";
+        let first = display(code);
+        assert_eq!(first, "Matrix 5×1: [\"a\"; \"alpha\"; \"b\"; \"mid\"; \"zeta\"]");
+        for _ in 0..10 {
+            assert_eq!(display(code), first);
+        }
+        let mut evaluator = evaluator("{ zeta = 1; alpha = 2; mid = 3 }");
+        evaluator.eval();
+        assert_eq!(evaluator.var_names(), ["alpha", "mid", "zeta"]);
+        // zipmap calls its operator in a scope of its own, which is not listed
+        assert_eq!(display("{ x = zipmap([1], [2], \"+\"); vars() }"), "Matrix 1×1: [\"x\"]");
+    }
+
+    #[test]
+    fn matrices_compare_as_whole_values() {
+        assert!(truth("[1, 2]==[1, 2]"));
+        assert!(!truth("[1, 2]==[1, 3]"));
+        // the shape and the units take part in the comparison
+        assert!(!truth("[1, 2]==[1; 2]"));
+        assert!(truth("[1|m|, 2|m|]==[100|cm|, 200|cm|]"));
+        assert!(!truth("[1|m|]==[1|s|]"));
+        assert!(truth("[\"a\", 1]==[\"a\", 1]"));
+        assert_eq!(error("[1, 2]==1"), "The '==' operator cannot compare a value of type 'Matrix' with one of type 'Number'.");
+    }
+
+    #[test]
+    fn nan_reductions_skip_nan_cells() {
+        // there is no nan literal, 0/0 gives one
+        assert!(truth("{ nan = 0/0; nanmean([1, nan, 3])==2 }"));
+        assert_eq!(real("{ nan = 0/0; nanmin([3, nan, 1]) }"), 1.0);
+        assert_eq!(real("{ nan = 0/0; nanmax([3, nan, 1]) }"), 3.0);
+        assert_eq!(real("{ nan = 0/0; nansum([1, nan, 3]) }"), 4.0);
+        assert_eq!(real("{ nan = 0/0; nansum([nan]) }"), 0.0);
+        assert_eq!(display("nanmean([1|m|, 3|m|])"), "2m");
+        assert_eq!(error("{ nan = 0/0; nanmean([nan, nan]) }"), "The 'nanmean' function needs at least one cell which is not NaN.");
+        assert_eq!(error("{ nan = 0/0; nanmin([nan]) }"), "The 'nanmin' function needs at least one cell which is not NaN.");
+        assert_eq!(error("nanmean(3)"), "The 'nanmean' function operates on matrices but an element of type 'Number' was found.");
+    }
+
+    #[test]
+    fn between_checks_a_closed_range() {
+        assert!(truth("between(5, 0, 10)"));
+        assert!(!truth("between(15, 0, 10)"));
+        // a boolean compared with a number counts as 1 or 0
+        assert!(truth("between(5, 0, 10)==1"));
+        assert!(truth("between(15, 0, 10)==0"));
+        // the bounds are included
+        assert!(truth("between(10, 0, 10)"));
+        assert!(truth("between(0, 0, 10)"));
+        assert!(truth("between(5|cm|, 0|m|, 1|m|)"));
+        assert_eq!(error("between(5|s|, 0|m|, 1|m|)"), "The 'between' function operates on quantities with the same units but '5s', '0m' and '1m' were found.");
+        assert_eq!(error("between(5, 10, 0)"), "The 'between' function needs a lower bound not greater than the upper one but '10' and '0' were found.");
+        assert_eq!(error("between(1i, 0, 1)"), "The 'between' function operates on real quantities but '0 + 1i', '0' and '1' were found.");
+    }
+
+    #[test]
+    fn out_of_range_indices_are_reported() {
+        let m = "m = [1, 2, 3; 4, 5, 6]";
+        assert_eq!(real(&format!("{{ {m}; m[2, 3] }}")), 6.0);
+        assert_eq!(real(&format!("{{ {m}; m[-1, -1] }}")), 6.0);
+        // an out of range column does not wrap to the next row
+        assert_eq!(error(&format!("{{ {m}; m[1, 4] }}")), "Index must not exceed Matrix bounds. Matrix 'm' is '2×3' but '[1, 4]' was found.");
+        assert_eq!(error(&format!("{{ {m}; m[3, 1] }}")), "Index must not exceed Matrix bounds. Matrix 'm' is '2×3' but '[3, 1]' was found.");
+        assert_eq!(error(&format!("{{ {m}; m[-3, 1] }}")), "Index must not exceed Matrix bounds. Matrix 'm' is '2×3' but '[-3, 1]' was found.");
+        assert_eq!(error(&format!("{{ {m}; m[1, -4] }}")), "Index must not exceed Matrix bounds. Matrix 'm' is '2×3' but '[1, -4]' was found.");
+        assert_eq!(error(&format!("{{ {m}; m[7] }}")), "Index must not exceed Matrix bounds. Matrix 'm' is '2×3' but '7' was found.");
+        assert!(error(&format!("{{ {m}; m[1, 0] }}")).starts_with("Matrix indices are 1-based"));
+    }
+
+    #[test]
+    fn for_loops_can_bind_the_position() {
+        // the positions follow the row-major order of linear indexing
+        assert_eq!(real("{ s = 0; for i, x in [1, 2; 3, 4] { s = s + (i - x)^2 }; s }"), 0.0);
+        assert_eq!(real("{ n = 0; for i, x in [1, 2; 3, 4] { n = n + 1 }; n }"), 4.0);
+        assert_eq!(real("{ s = 0; for i, x in [10, 20, 30] { s = s + i*x }; s }"), 140.0);
+        assert_eq!(real("{ m = [5, 6; 7, 8]; s = 0; for i, x in m { s = s + (m[i] - x)^2 }; s }"), 0.0);
+        assert!(error("for i, 3 in [1] {}").starts_with("The first element after a 'for' keyword must be a valid variable name."));
+    }
+
+    #[test]
+    fn zip_pairs_two_vectors_as_columns() {
+        assert_eq!(matrix("zip([1, 2, 3], [4, 5, 6])"), (2, 3, vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0]));
+        assert_eq!(display("zip([1, 2, 3], [4, 5, 6])"), "Matrix 3×2: [1, 4; 2, 5; 3, 6]");
+        assert_eq!(matrix("zip([1; 2], [3, 4])"), (2, 2, vec![1.0, 3.0, 2.0, 4.0]));
+        assert_eq!(display("zip([1|m|], [\"a\"])"), "Matrix 1×2: [1m, \"a\"]");
+        assert_eq!(error("zip([1, 2], [1, 2, 3])"), "The 'zip' function operates on vectors with the same length but lengths 2 and 3 were found.");
+        assert_eq!(error("zip([1, 2; 3, 4], [1, 2])"), "The 'zip' function operates on vectors but a matrix 2×2 was found.");
+    }
+
+    #[test]
+    fn unique_keeps_the_first_occurrences() {
+        assert!(truth("unique([1, 2, 2, 3, 1])==[1; 2; 3]"));
+        assert_eq!(matrix("unique([3, 1, 3, 2, 1])"), (1, 3, vec![3.0, 1.0, 2.0]));
+        // equal quantities written in different units are the same value
+        assert_eq!(display("unique([1|m|, 100|cm|, 2|m|])"), "Matrix 2×1: [1m; 2m]");
+        assert_eq!(matrix("unique(range(1, 1))"), (1, 0, vec![]));
+        assert_eq!(error("unique([1|m|, 1|s|])"), "The 'unique' function operates on quantities with the same units but '1m' and '1s' were found.");
+        assert_eq!(error("unique([1, 2; 2, 3])"), "The 'unique' function operates on vectors but a matrix 2×2 was found.");
+    }
+}
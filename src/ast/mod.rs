@@ -98,15 +98,29 @@ impl Tree {
     fn is_while(&self) -> bool {
         match &self.node { Node::Operator(str) =>  { !self.has_value && str == "while" }, _ => false }
     }
+    fn is_bitwise(&self) -> bool {
+        match &self.node { Node::Operator(str) =>  { !self.has_value && (str == "band" || str == "bor" || str == "bxor" || str == "shl" || str == "shr") }, _ => false }
+    }
     fn is_for(&self) -> bool {
         match &self.node { Node::Operator(str) =>  { !self.has_value && str == "for" }, _ => false }
     }
+    // whether the value of the tree comes from an assignment, 'x = 5' or '{ y = 2; x = y }'
+    pub fn ends_with_assignment(&self) -> bool {
+        match &self.node {
+            Node::Operator(str) => str == "=",
+            Node::Block => self.children.last().is_some_and(|c| c.ends_with_assignment()),
+            _ => false,
+        }
+    }
+    fn is_comma(&self) -> bool {
+        match &self.node { Node::Keyword(str) =>  { str == "," }, _ => false }
+    }
 }
 
-impl std::convert::Into<Tree> for Node {
-    fn into(self) -> Tree {
+impl std::convert::From<Node> for Tree {
+    fn from(val: Node) -> Self {
         Tree {
-            node: self,
+            node: val,
             children: Vec::new(),
             has_value: false,
         }
@@ -140,6 +154,35 @@ fn apply_binary_operation_to_level(level: &mut Vec<Tree>, node_is_wanted_operati
     }
 }
 
+fn apply_right_associative_binary_operation_to_level(level: &mut Vec<Tree>, node_is_wanted_operation: fn(&Tree) -> bool) {
+    if level.len() < 3 { return; }
+    // we walk backwards so that A = B = C is parsed as A = (B = C)
+    let mut i = level.len() - 2;
+    while i >= 1 {
+        if node_is_wanted_operation(&level[i]) {
+            let right = level.remove(i + 1);
+            let left = level.remove(i - 1);
+            // now the operator has changed index i -> i - 1
+            let middle = &mut level[i - 1];
+            if left.has_value && right.has_value {
+                middle.children.push(left);
+                middle.children.push(right);
+                middle.has_value = true;
+                // level = A B C D E F G H I
+                //                 ^^-^^ -> N
+                // level = A B C D N H I
+                //             ^^-^^
+            }else{
+                panic!("A binary operator needs valued expressions to its sides. Found \nleft:\n{:?}\noperator:\n{:?} \nright:\n{:?}", left, middle, right);
+            }
+        }
+        if i < 2 { break; }
+        i -= 1;
+        // the level has shrunk, the operator we might apply must have a right side
+        if i > level.len() - 2 { i = level.len() - 2; }
+    }
+}
+
 fn _apply_prefixed_unary_operation_to_level(level: &mut Vec<Tree>, node_is_wanted_operation: fn(&Tree) -> bool) {
     if level.len() < 2 { return; }
     // the unary prefixed operator cannot be the last element
@@ -168,7 +211,9 @@ fn _apply_prefixed_unary_operation_to_level(level: &mut Vec<Tree>, node_is_wante
     }
 }
 
-fn apply_all_prefixed_unary_operations_to_level(level: &mut Vec<Tree>) {
+// the unary + and - bind looser than ^ (-2^2 == -4), so before the elevation level
+// they are left alone when their operand is raised to a power: defer_signs_before_pow
+fn apply_all_prefixed_unary_operations_to_level(level: &mut Vec<Tree>, defer_signs_before_pow: bool) {
     if level.len() < 2 { return; }
     // the unary prefixed operator cannot be the last element
     let mut i: i32 = (level.len() as i32) - 2; 
@@ -176,7 +221,14 @@ fn apply_all_prefixed_unary_operations_to_level(level: &mut Vec<Tree>) {
     while i >= 0 { // the unary prefixed operator can also be the first element
         let none_tree = Tree { node: Node::None, children: Vec::new(), has_value: false};
         let left_ref = level.get((i-1) as usize).unwrap_or(&none_tree);
-        if 
+        let is_sign = level[i as usize].is_sum() || level[i as usize].is_sub();
+        if defer_signs_before_pow && is_sign && !left_ref.is_pow() && operand_precedes_pow(level, (i+1) as usize) {
+            // -2^2: the sign is applied after the elevation, 2^-2 stays as it is
+            i -= 1;
+        }else if defer_signs_before_pow && !level[(i+1) as usize].has_value && (level[(i+1) as usize].is_sum() || level[(i+1) as usize].is_sub()) {
+            // !-2^2: the operator waits for the deferred sign
+            i -= 1;
+        }else if 
             level[i as usize].is_bang() || // not(!) 
             ( ( left_ref.is_operator() || left_ref.is_none() ) && level[i as usize].is_sum() ) || // +(unary)
             ( ( left_ref.is_operator() || left_ref.is_none() ) && level[i as usize].is_sub() ) || // -(unary)
@@ -204,6 +256,15 @@ fn apply_all_prefixed_unary_operations_to_level(level: &mut Vec<Tree>) {
     }
 }
 
+// whether the element at index operand, followed by its postfixed operators, is the base of a ^
+fn operand_precedes_pow(level: &[Tree], operand: usize) -> bool {
+    let mut j = operand + 1;
+    while j < level.len() && (level[j].is_question() || level[j].is_unitblock()) {
+        j += 1;
+    }
+    j < level.len() && level[j].is_pow()
+}
+
 fn apply_postfixed_unary_operation_to_level(level: &mut Vec<Tree>, node_is_wanted_operation: fn(&Tree) -> bool) {
     if level.len() < 2 { return; }
     let mut i = 1;
@@ -346,6 +407,16 @@ fn apply_for_statements_to_level(level: &mut Vec<Tree>) {
     while i >= 0 {
         if level[i as usize].is_for() 
         {
+            // for i, x in matrix {} also binds the position of x to i
+            // ^^^ ^^ 
+            let position = if level.len() > (i+2) as usize && level[(i+2) as usize].is_comma() {
+                level.remove((i+2) as usize);
+                let position = level.remove((i+1) as usize);
+                if level.len() < (i+5) as usize { panic!("A 'for' statement is incomplete, it should look like 'for i, x in matrix {{}}'.") }
+                Some(position)
+            }else{
+                None
+            };
             // for x in matrix {}
             // ^^^ ^ ^^ ^^^^^^ ^^
             //  0  1 2     3   4
@@ -359,7 +430,13 @@ fn apply_for_statements_to_level(level: &mut Vec<Tree>) {
             if key_name == "in" {
             if right3.has_value {
             if let Node::Block = &right4.node {
-                if right4.has_value == false { panic!("The second element after the 'in' keyword of a 'for' statement must be a valued block. Found '{:?}' instead, which has no value.", right4)}
+                if !right4.has_value { panic!("The second element after the 'in' keyword of a 'for' statement must be a valued block. Found '{:?}' instead, which has no value.", right4)}
+                if let Some(position) = position {
+                    if !matches!(position.node, Node::Variable(_)) {
+                        panic!("The first element after a 'for' keyword must be a valid variable name. Found {:?} instead.", position);
+                    }
+                    middle.children.push(position);
+                }
                 middle.children.push(right1);
                 middle.children.push(right3);
                 middle.children.push(right4);
@@ -387,8 +464,29 @@ fn apply_for_statements_to_level(level: &mut Vec<Tree>) {
     }
 }
 
+// splits the lexems at each comma which is not nested inside parenthesis or brackets
+fn split_top_level_commas(lexems: &[Lexem]) -> Vec<&[Lexem]> {
+    let mut elements = Vec::new();
+    let mut depth = 0;
+    let mut from = 0;
+    for i in 0..lexems.len() {
+        match lexems[i] {
+            Lexem::LeftPar | Lexem::LeftBracket | Lexem::LeftSqBracket => { depth += 1; }
+            Lexem::RightPar | Lexem::RightBracket | Lexem::RightSqBracket => { depth -= 1; }
+            Lexem::Comma
+                if depth == 0 => {
+                    elements.push(&lexems[from..i]);
+                    from = i + 1;
+                }
+            _ => (),
+        }
+    }
+    elements.push(&lexems[from..]);
+    elements
+}
+
 pub fn ast(lexems: &[Lexem]) -> Tree{    
-    if lexems.len() == 0 {
+    if lexems.is_empty() {
         return Tree {
             node: Node::None,
             children: Vec::new(),
@@ -398,7 +496,14 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
 
     let mut level: Vec<Tree> = Vec::new();
     let mut i = 0;
+    // whether the last element is a number or a parenthesis, which multiply what follows them: 2(1+1)
+    let mut after_factor = false;
     while i < lexems.len() {
+        let start = i;
+        if after_factor && matches!(lexems[i], Lexem::Identifier(_) | Lexem::LeftPar) {
+            // IMPLICIT MULTIPLICATION
+            level.push(Node::Operator(String::from("*")).into());
+        }
         let tree = match &lexems[i] {
             Lexem::Number(num, dec) => {
                 i += 1;
@@ -440,7 +545,17 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
                 if parcount != 0 {
                     panic!("Each opening parenthesis needs a corresponding closing parenthesis. Parcount: {parcount}");
                 }else{
-                    ast(&lexems[from+1..to])
+                    let elements = split_top_level_commas(&lexems[from+1..to]);
+                    if elements.len() == 1 {
+                        ast(&lexems[from+1..to])
+                    }else{
+                        // tuple: (a, b, c) is stored as a row vector
+                        Tree {
+                            node: Node::MatrixBlock(elements.len(), 1),
+                            children: elements.iter().map(|e| ast(e)).collect(),
+                            has_value: true,
+                        }
+                    }
                 }
             },
             Lexem::LeftSqBracket => {
@@ -537,13 +652,11 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
                         Lexem::RightBracket => { bracketcount -= 1; }
                         Lexem::LeftSqBracket => { sqbracketcount += 1; }
                         Lexem::RightSqBracket => { sqbracketcount -= 1; }
-                        Lexem::SemiColon => {
-                            if bracketcount == 1 && sqbracketcount == 0 {
-                                // everything until but not including the semicolon
-                                elements.push(ast(&lexems[from..i]));
-                                // everything from but not including the semicolon
-                                from = i + 1;
-                            }
+                        Lexem::SemiColon if bracketcount == 1 && sqbracketcount == 0 => {
+                            // everything until but not including the semicolon
+                            elements.push(ast(&lexems[from..i]));
+                            // everything from but not including the semicolon
+                            from = i + 1;
                         }
                         _ => (),
                     }
@@ -740,7 +853,7 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
             Lexem::UnitBlock(unit, factor, shift) => {
                 i += 1;
                 Tree {
-                    node: Node::UnitBlock(unit.clone(), factor.clone(), shift.clone()),
+                    node: Node::UnitBlock(unit.clone(), *factor, *shift),
                     children: Vec::new(),
                     has_value: false,
                 }
@@ -763,7 +876,14 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
                 panic!("Closing square bracket with no matching opening square bracket.")
             }
             Lexem::Comma => {
-                panic!("Comma found outside of any function call or matrix.");
+                // the only comma outside of function calls and matrices is the one of 'for i, x in matrix'
+                let n = level.len();
+                if n >= 2 && level[n - 2].is_for() && matches!(level[n - 1].node, Node::Variable(_)) {
+                    i += 1;
+                    Node::Keyword(String::from(",")).into()
+                }else{
+                    panic!("Comma found outside of any function call or matrix.");
+                }
             }
             Lexem::SemiColon => {
                 // dbg!(lexems);
@@ -772,14 +892,14 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
             }
         };
         level.push(tree);
-        
+        after_factor = matches!(lexems[start], Lexem::Number(_, _) | Lexem::LeftPar);
     }
 
     // I don't use this method anymore because it's harder to deal with the special case of +(unary) and -(unary)
     // _apply_prefixed_unary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_bang() });
 
     // not(!), +(unary), -(unary), $(value), &(error)
-    apply_all_prefixed_unary_operations_to_level(&mut level);
+    apply_all_prefixed_unary_operations_to_level(&mut level, true);
 
     // question(?)
     apply_postfixed_unary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_question() });
@@ -790,6 +910,9 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
     // elevation
     apply_binary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_pow() });
 
+    // +(unary), -(unary) of a power: -2^2
+    apply_all_prefixed_unary_operations_to_level(&mut level, false);
+
     // prod, div
     apply_binary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_prod() || tree.is_div() });
 
@@ -799,6 +922,9 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
     // sum, sub
     apply_binary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_sum() || tree.is_sub() });
 
+    // band, bor, bxor, shl, shr
+    apply_binary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_bitwise() });
+
     // eq(==), gt(>), gte(>=), lt(<), lte(<=)
     apply_binary_operation_to_level(&mut level, |tree: &Tree| -> bool { 
         tree.is_equal_equal() || tree.is_greater() || tree.is_greater_equal() || 
@@ -823,12 +949,12 @@ pub fn ast(lexems: &[Lexem]) -> Tree{
     // for
     apply_for_statements_to_level(&mut level);
 
-    // assign(=)
-    apply_binary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_assign() });
+    // assign(=), right associative: a = b = 5
+    apply_right_associative_binary_operation_to_level(&mut level, |tree: &Tree| -> bool { tree.is_assign() });
 
     if level.len() > 1 {
         panic!("The parsing couldn't finish. The reduced level resulted in:\n{:?}", level);
-    }else if level.len() == 0 {
+    }else if level.is_empty() {
         panic!("The parsing couldn't finish. The reduced level resulted empty");
     }
 
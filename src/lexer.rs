@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use unicode_segmentation::UnicodeSegmentation;
 use crate::quantity::Unit;
 
@@ -42,10 +43,19 @@ impl std::fmt::Display for Lexem {
 pub struct Lexer {
     pub text: String,
     pub lexems: Vec<Lexem>,
+    // stack of the files being lexed, the last one contains the text
+    // used to resolve relative includes and to detect include cycles
+    pub files: Vec<PathBuf>,
 }
+impl Default for Lexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Lexer {
     pub fn new() -> Lexer { Lexer{
-        text: String::new(), lexems: vec![],
+        text: String::new(), lexems: vec![], files: vec![],
     }}
 
     pub fn lex(&mut self) {
@@ -55,9 +65,14 @@ impl Lexer {
         let mut i = 0;
 
         let string_operators = vec![
-            "or", "and", "nand", "xor", "if", "else", "pm", "while", "for"
+            "or", "and", "nand", "xor", "if", "else", "pm", "while", "for",
+            "band", "bor", "bxor", "shl", "shr"
         ];
-        let keywords = vec![
+        // these operators can also be called as functions: band(12, 10)
+        let function_operators = [
+            "band", "bor", "bxor", "shl", "shr"
+        ];
+        let keywords = [
             "in" // the "in" of "for x in matrix"
         ];
 
@@ -127,16 +142,42 @@ impl Lexer {
                     }else if chars[i] == "\\" {
                         match chars[i + 1] {
                             "n" => {
-                                i += 1; str_block.push_str("\n");
+                                i += 1; str_block.push('\n');
                             }
                             "t" => {
-                                i += 1; str_block.push_str("\t");
+                                i += 1; str_block.push('\t');
                             }
                             "\"" => {
-                                i += 1; str_block.push_str("\"");
+                                i += 1; str_block.push('"');
+                            }
+                            "r" => {
+                                i += 1; str_block.push('\r');
+                            }
+                            "0" => {
+                                i += 1; str_block.push('\0');
+                            }
+                            "u" => {
+                                // unicode escape: \u{1F600}
+                                if chars[i + 2] != "{" {
+                                    panic!("Unicode escapes must be written as '\\u{{...}}' with an hexadecimal code.");
+                                }
+                                let mut j = i + 3;
+                                let mut code = String::new();
+                                while j < n && chars[j] != "}" && chars[j] != "\"" && chars[j] != "\0" {
+                                    code.push_str(chars[j]);
+                                    j += 1;
+                                }
+                                if chars[j] != "}" {
+                                    panic!("Unicode escape '\\u{{{code}' is missing a closing '}}'.");
+                                }
+                                match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                                    Some(c) => { str_block.push(c); }
+                                    None => { panic!("'\\u{{{code}}}' is not a valid unicode escape."); }
+                                }
+                                i = j;
                             }
                             // "\\" is done in evaluation
-                            _ => { str_block.push_str("\\"); }
+                            _ => { str_block.push('\\'); }
                         }
                     }else{
                         str_block.push_str(chars[i]);
@@ -229,18 +270,33 @@ impl Lexer {
                 let mut number = String::from(char);
                 let mut decorator = String::new();
                 let mut inside_decorator = false;
+                let mut inside_exponent = false;
+                // a digit, or a sign and a digit, make an 'e' the exponent of the number rather than a decorator
+                let starts_exponent = |k: usize| {
+                    let digit = |k: usize| k < n && "1234567890".contains(chars[k]);
+                    digit(k) || ((chars[k] == "+" || chars[k] == "-") && digit(k + 1))
+                };
                 let mut j = i + 1;
                 // consume all letters after these
                 'consumerN: while j < n {
                     char = chars[j];
-                    if !inside_decorator && "1234567890.".find(char).is_some() {
+                    if !inside_decorator && ("1234567890".contains(char) || (char == "." && !inside_exponent)) {
                         // this char is part of the number
                         number.push_str(char);
                         j += 1;
+                    }else if !inside_decorator && !inside_exponent && (char == "e" || char == "E") && starts_exponent(j + 1) {
+                        // scientific notation: 3e5, 1.5E-3
+                        inside_exponent = true;
+                        number.push('e');
+                        if chars[j + 1] == "+" || chars[j + 1] == "-" {
+                            number.push_str(chars[j + 1]);
+                            j += 1;
+                        }
+                        j += 1;
                     }else if !inside_decorator && "'".find(char).is_some() {
                         // this character can be skipped example: 1'000 == 1000
                         j += 1;
-                    }else if "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789°Ω%πµμ".find(char).is_some() {
+                    }else if "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789°Ω%‰πµμ".find(char).is_some() {
                         // this char is part of the number's decorator
                         inside_decorator = true;
                         decorator.push_str(char);
@@ -266,7 +322,9 @@ impl Lexer {
                         j += 1;
                     }else{
                         // the identifier is finished
-                        if string_operators.contains(&&word[..]) {
+                        if function_operators.contains(&&word[..]) && chars[j] == "(" {
+                            self.lexems.push(Lexem::Identifier(word));
+                        }else if string_operators.contains(&&word[..]) {
                             self.lexems.push(Lexem::Operator(word));
                         }else if keywords.contains(&&word[..]){
                             self.lexems.push(Lexem::Keyword(word));
@@ -281,11 +339,138 @@ impl Lexer {
                 panic!("Syntax error at character number {}: '{}'", i, char);
             }
         }
+
+        self.resolve_includes();
+    }
+
+    // replaces each 'include "path"' with the lexems of that file
+    fn resolve_includes(&mut self) {
+        let lexems = std::mem::take(&mut self.lexems);
+        let mut iter = lexems.into_iter().peekable();
+        while let Some(lexem) = iter.next() {
+            let is_include = match (&lexem, iter.peek()) {
+                (Lexem::Identifier(word), Some(Lexem::StringBlock(_))) => word == "include",
+                _ => false,
+            };
+            if !is_include {
+                self.lexems.push(lexem);
+                continue;
+            }
+            let path_str = match iter.next() { Some(Lexem::StringBlock(s)) => s, _ => unreachable!() };
+            // relative paths are relative to the including file
+            let mut path = match self.files.last().and_then(|f| f.parent()) {
+                Some(dir) => dir.join(&path_str),
+                None => PathBuf::from(&path_str),
+            };
+            path = path.canonicalize().unwrap_or_else(|e| panic!("Unable to include '{path_str}': {e}"));
+            if self.files.iter().any(|f| f.canonicalize().map(|f| f == path).unwrap_or(false)) {
+                panic!("Include cycle detected: '{path_str}' is already being included.");
+            }
+            let mut lexer = Lexer::new();
+            lexer.text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Unable to include '{path_str}': {e}"));
+            lexer.files = self.files.clone();
+            lexer.files.push(path);
+            lexer.lex();
+            self.lexems.append(&mut lexer.lexems);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lexes the text as if it were the content of the given file
+    fn lex_file(text: &str, file: Option<PathBuf>) -> Vec<String> {
+        let mut lexer = Lexer::new();
+        lexer.text = String::from(text);
+        lexer.files.extend(file);
+        lexer.lex();
+        lexer.lexems.iter().map(|lexem| format!("{lexem}")).collect()
+    }
+
+    // a directory in the temporary directory that no other test uses
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tera_lang_{}_{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // the content of the single string literal in the code
+    fn string_literal(code: &str) -> String {
+        let mut lexer = Lexer::new();
+        lexer.text = String::from(code);
+        lexer.lex();
+        match &lexer.lexems[..] {
+            [Lexem::StringBlock(s)] => s.clone(),
+            other => panic!("'{code}' was lexed as {other:?} instead of a string"),
+        }
     }
 
-    pub fn print(&self) {
-        for lref in self.lexems.iter() {
-            print!("{} ", lref);
+    #[test]
+    fn include_inlines_the_lexems_of_the_file() {
+        let dir = temp_dir("include");
+        std::fs::write(dir.join("lib.tera"), "x = 21;").unwrap();
+        std::fs::write(dir.join("main.tera"), "{ include \"lib.tera\" y = 2*x; y }").unwrap();
+        // relative paths are resolved from the including file
+        let main = dir.join("main.tera");
+        let text = std::fs::read_to_string(&main).unwrap();
+        assert_eq!(lex_file(&text, Some(main)), lex_file("{ x = 21; y = 2*x; y }", None));
+        // the included definitions are visible to the including code
+        let code = format!("{{ include \"{}\" 2*x }}", dir.join("lib.tera").display());
+        match crate::ast::eval::Evaluator::eval_str(&code) {
+            Ok(crate::ast::eval::RValue::Number(n)) => assert_eq!(n, 42.0),
+            other => panic!("unexpected result: {other:?}"),
         }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Include cycle detected: 'a.tera' is already being included.")]
+    fn include_rejects_cycles() {
+        let dir = temp_dir("include_cycle");
+        std::fs::write(dir.join("a.tera"), "include \"b.tera\"").unwrap();
+        std::fs::write(dir.join("b.tera"), "include \"a.tera\"").unwrap();
+        lex_file("include \"a.tera\"", Some(dir.join("main.tera")));
+    }
+
+    #[test]
+    fn string_literals_translate_escapes() {
+        assert_eq!(string_literal(r#""a\nb\tc\"d""#), "a\nb\tc\"d");
+        assert_eq!(string_literal(r#""a\rb\0c""#), "a\rb\0c");
+        assert_eq!(string_literal(r#""\u{41}\u{e9}\u{1F600}""#), "Aé😀");
+        // '\\' is left to the evaluation
+        assert_eq!(string_literal(r#""a\\b""#), "a\\\\b");
+    }
+
+    #[test]
+    #[should_panic(expected = "'\\u{110000}' is not a valid unicode escape.")]
+    fn unicode_escapes_must_be_valid_characters() {
+        string_literal(r#""\u{110000}""#);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unicode escape '\\u{41' is missing a closing '}'.")]
+    fn unicode_escapes_must_be_closed() {
+        string_literal(r#""\u{41""#);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unicode escapes must be written as '\\u{...}' with an hexadecimal code.")]
+    fn unicode_escapes_need_braces() {
+        string_literal(r#""\u41""#);
+    }
+
+
+    #[test]
+    fn numbers_keep_exponents_apart_from_decorators() {
+        assert_eq!(lex_file("3e5", None), ["NUM{3e5, \"\"}"]);
+        assert_eq!(lex_file("1.5E-3", None), ["NUM{1.5e-3, \"\"}"]);
+        assert_eq!(lex_file("2.5e3m", None), ["NUM{2.5e3, \"m\"}"]);
+        // an 'e' not followed by the exponent is a decorator
+        assert_eq!(lex_file("3e", None), ["NUM{3, \"e\"}"]);
+        assert_eq!(lex_file("3m", None), ["NUM{3, \"m\"}"]);
+        assert_eq!(lex_file("3alpha", None), ["NUM{3, \"alpha\"}"]);
+        assert_eq!(lex_file("3 alpha", None), ["NUM{3, \"\"}", "ID{alpha}"]);
     }
 }
@@ -0,0 +1,3 @@
+pub mod lexer;
+pub mod ast;
+pub mod quantity;
@@ -1,42 +1,89 @@
-mod lexer;
-use lexer::Lexer;
-
-mod ast;
-mod quantity;
+use tera_lang::lexer::Lexer;
+use tera_lang::ast;
+use tera_lang::ast::eval::RValue;
 
 use std::fs;
+use std::path::PathBuf;
 use std::time::{Instant};
 
 use std::env;
 
+// values which are not worth printing at the end of a program:
+// nothing, or what is left by loops whose body only prints
+fn is_silent(res: &RValue) -> bool {
+    match res {
+        RValue::Void => true,
+        RValue::Matrix(_, _, v) => v.iter().all(is_silent),
+        _ => false,
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let code;
-    if args.len() > 1 {
-        code = fs::read_to_string(&(args[1])[..]);
-    }else{
+    // --quiet (or -q) anywhere before '--' suppresses the timing report,
+    // the arguments after '--' are passed to the script as they are
+    let mut quiet = false;
+    let mut args: Vec<String> = Vec::new();
+    let mut raw_args = env::args();
+    for arg in raw_args.by_ref() {
+        if arg == "--" {
+            break;
+        }else if arg == "--quiet" || arg == "-q" {
+            quiet = true;
+        }else{
+            args.push(arg);
+        }
+    }
+    args.extend(raw_args);
+    if args.len() < 2 {
         panic!("Source code path missing");
     }
-    let code = code.expect("Unable to read the source file");
+    // tera_lang --eval "2|m| + 3|cm|" runs the given code instead of a file
+    let inline = args[1] == "--eval" || args[1] == "-e";
+    if inline && args.len() < 3 {
+        panic!("The '{}' flag must be followed by the code to run", args[1]);
+    }
 
     let mut lexer = Lexer::new();
-    lexer.text = code.clone();
+    if inline {
+        lexer.text = args[2].clone();
+    }else{
+        lexer.text = fs::read_to_string(&(args[1])[..]).expect("Unable to read the source file");
+        lexer.files.push(PathBuf::from(&args[1]));
+    }
     lexer.lex();
 
     let abst = ast::ast(&lexer.lexems);
+    let ends_with_assignment = abst.ends_with_assignment();
     let mut evaluator = ast::eval::Evaluator::from_tree(abst);
 
+    // the arguments following the source path (or the inline code) are available to the script
+    let first_arg = if inline { 3 } else { 2 };
+    let script_args: Vec<RValue> = args[first_arg..].iter().map(|a| RValue::String(a.clone())).collect();
+    evaluator.set_var(String::from("args"), RValue::Matrix(1, script_args.len(), script_args));
+
     let iterations = 1;
     let now = Instant::now();
     
+    let mut res = RValue::Void;
     for _ in 1..=iterations {
-        let _res = evaluator.eval();
+        res = evaluator.eval();
         // println!("\n\n{} = {}", lexer.text, res);   
     }
 
     let elapsed_time = now.elapsed();
     let time = elapsed_time.as_nanos() as f64 / 1e3;
-    println!("Running took {}µs which is {}µs per iteration.", time, time / iterations as f64);
+
+    // like a calculator, the value of the program is printed unless it has none
+    // or it is the one of a final assignment, 'x = 5'
+    if !is_silent(&res) && !ends_with_assignment {
+        println!("{res}");
+    }
+
+    // the report goes to stderr so that the output of the script can be piped,
+    // one-off calculations only print their result
+    if !quiet && !inline {
+        eprintln!("Running took {}µs which is {}µs per iteration.", time, time / iterations as f64);
+    }
 
     /*
     let x = Quantity{re: 1.0, im: 0.0, vre: 0.1*0.1, vim: 0.0, unit: quantity::Unit::unitless()};
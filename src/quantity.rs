@@ -2,6 +2,29 @@ use std::{ops};
 use unicode_segmentation::UnicodeSegmentation;
 
 
+// error produced when a unit expression cannot be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitError {
+    pub message: String,
+}
+impl std::fmt::Display for UnitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for UnitError {}
+
+macro_rules! unit_error {
+    ($($arg:tt)*) => {
+        return Err(UnitError { message: format!($($arg)*) })
+    };
+}
+
+// the closest f64 to 10^exponent
+fn decimal_factor(exponent: i32) -> f64 {
+    format!("1e{exponent}").parse().unwrap()
+}
+
 // SI unit
 #[derive(Debug, Clone, PartialEq)]
 pub struct Unit {
@@ -21,188 +44,187 @@ impl Unit {
         *self == Unit { mole: 0, metre: 0, second: 0, kilogram: 0, kelvin: 0, ampere: 0, candela: 0 }
     }
     pub fn parse_single_unit(text: &str) -> (Unit, f64, f64) {
+        Unit::try_parse_single_unit(text).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_parse_single_unit(text: &str) -> Result<(Unit, f64, f64), UnitError> {
         let chars = text.graphemes(true).collect::<Vec<&str>>();
-        let mut unit = Unit::unitless();
-        let mut factor;
-        let mut shift: f64 = 0.0;
+        if chars.is_empty() {
+            unit_error!("Unknown unit expression '{}' because it is empty", text);
+        }
 
         // find the end of the stringy part
         let mut sepid = 0;
-        for i in 0..chars.len() {
-            if "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ°µμ".find(chars[i]).is_some() {
+        for (i, c) in chars.iter().enumerate() {
+            if "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ°µμΩπ%‰".contains(c) {
                 sepid = i;
             }else{
                 break;
             }
         }
 
-        let unit_str = &chars[0..=sepid];
-        let mut skip = 1;
-
-        match unit_str[0] {
-            "Q"  => {factor = 1e30;}
-            "R"  => {factor = 1e27;}
-            "Y"  => {factor = 1e24;}
-            "Z"  => {factor = 1e21;}
-            "E"  => {factor = 1e18;}
-            "P"  => {factor = 1e15;}
-            "T"  => {factor = 1e12;}
-            "G"  => {factor = 1e9;}
-            "M"  => {factor = 1e6;}
-            "k"  => {factor = 1e3;}
-            "h"  => {factor = 1e2;}
-            "d"  => {
-                if chars.len() >= 2 {
-                    if chars[0] == "d" && chars[1] == "a" {
-                        skip = 2;
-                        factor = 10.0; // da
-                    }else{
-                        factor = 0.1; // d
-                    }
-                }else{
-                    panic!("Unknown symbol 'd'");
-                }
-            }
-            "c"  => {factor = 1.0/1e2}
-            "m"  => {
-                if chars.len() >= 2 {
-                    if (chars[0] == "m" && chars[1] == "u") || (chars[0] == "m" && chars[1] == "i") {
-                        skip = 2;
-                        factor = 1.0/1e6; // mu == mi == µ
-                    }else{
-                        factor = 1.0/1e3; // m
+        let unit_name = chars[0..=sepid].join("");
+        // a whole unit name wins over a prefix: 'min' is the minute, 'Pa' the pascal, 'cd' the candela
+        let (mut unit, mut factor, shift) = match Unit::base_unit(&unit_name) {
+            Some(res) => res,
+            None => match Unit::prefixed_unit(&unit_name) {
+                Some(res) => res,
+                None => {
+                    if Unit::prefix_factor(&unit_name).is_some() {
+                        unit_error!("Unknown unit expression '{}' because the prefix '{}' is not followed by a unit", text, unit_name);
                     }
-                }else{
-                    factor = 1.0/1e3; // m
-                }
-            }
-            "µ" | "μ" => {factor = 1.0/1e6;} // warning: those are two different characters
-            "n"  => {factor = 1.0/1e9;}
-            "p"  => {factor = 1.0/1e12;}
-            "f"  => {factor = 1.0/1e15;}
-            "a"  => {factor = 1.0/1e18;}
-            "z"  => {factor = 1.0/1e21;}
-            "y"  => {factor = 1.0/1e24;}
-            "r"  => {factor = 1.0/1e27;}
-            "q"  => {factor = 1.0/1e30;}
-            _    => {
-                skip = 0; 
-                factor = 1.0;
-            }
-        }; 
-
-        // SPECIAL CASES
-        let joined_unit_str = unit_str.join("");
-        if joined_unit_str == "m" {
-            // should be the meter unit not the 'milli'
-            factor = 1.0; 
-            skip = 0;
-        }
-        if joined_unit_str == "mol" {
-            // should be the mole unit not the 'milli'
-            factor = 1.0;
-            skip = 0;
-        }
-        if joined_unit_str == "cd" {
-            // should be the candela unit not the 'centi'
-            factor = 1.0;
-            skip = 0;
-        }
-        if joined_unit_str == "rad" {
-            // should be the rad unit not the 'r'
-            factor = 1.0;
-            skip = 0;
-        }
-        if joined_unit_str == "°C" {
-            // centigrade degrees
-            shift = -273.15;
-            factor = 1.0;
-            skip = 0;
-        }
-        if joined_unit_str == "°" || joined_unit_str == "deg" {
-            // degrees
-            factor = std::f64::consts::PI / 180.0;
-            skip = 0;
-        }
-        if joined_unit_str == "%" {
-            // percentage
-            factor = 0.01;
-            skip = 0;
-        }
-        if joined_unit_str == "pi" || joined_unit_str == "π" {
-            // π
-            factor = std::f64::consts::PI;
-            skip = 0;
-        }
-
-        if unit_str.len() > skip {
-            match &unit_str[skip..].join("")[..] {
-                // SI base units
-                "m" => { unit.metre = 1; }
-                "s" => { unit.second = 1; }
-                "g" => { unit.kilogram = 1; factor = factor / 1000.0; }
-                "K" => { unit.kelvin = 1; }
-                "cd" => { unit.candela = 1; }
-                "mol" => { unit.mole = 1; }
-                "A" => { unit.ampere = 1; }
-
-                // scales
-                "°C" => { unit.kelvin = 1; }
-                
-                // unitless
-                "°" | "deg" | "%" | "pi" | "π"=> { }
-
-                // not SI
-                "L" => { unit.metre = 3; factor = factor / 1000.0; }
-                "eV" => { factor *= 1.602176565e-19; unit.kilogram = 1; unit.metre = 2; unit.second = -2; }
-
-                // derived units
-                "Hz" => { unit.second = -1; }
-                "N" => { unit.kilogram = 1; unit.metre = 1; unit.second = -2; }
-                "Pa" => { unit.kilogram = 1; unit.metre = -1; unit.second = -2; }
-                "J" => { unit.kilogram = 1; unit.metre = 2; unit.second = -2; }
-                "W" => { unit.kilogram = 1; unit.metre = 2; unit.second = -3; }
-                "C" => { unit.second = 1; unit.ampere = 1; }
-                "V" => { unit.kilogram = 1; unit.metre = 2; unit.second = -3; unit.ampere = -1; }
-                "F" => { unit.kilogram = -1; unit.metre = -2; unit.second = 4; unit.ampere = 2; }
-                "ohm" | "Ω" => { unit.kilogram = 1; unit.metre = 2; unit.second = -3; unit.ampere = -2; }
-                "S" => { unit.kilogram = -1; unit.metre = -2; unit.second = 3; unit.ampere = 2; }
-                "Wb" => { unit.kilogram = 1; unit.metre = 2; unit.second = -2; unit.ampere = -1; }
-                "Tesla" => { unit.kilogram = 1; unit.second = -2; unit.ampere = -1; }
-                "H" => { unit.kilogram = 1; unit.metre = 2; unit.second = -2; unit.ampere = -2; }
-                "lm" => { unit.candela = 1; }
-                "lx" => { unit.candela = 1; unit.metre = -2; }
-                "rad" | "sr" => { }
-                _ => {
-                    panic!("Unknown unit expression '{}' due to unknown unit '{}'", text, unit_str[skip..].join("") );
+                    unit_error!("Unknown unit expression '{}' due to unknown unit '{}'", text, unit_name);
                 }
             }
-        }
+        };
 
-        if chars.len() - 1 >= sepid + 1 { 
+        if chars.len() > sepid + 1 { 
             let exponent_str = &chars[sepid+1..].join("");
             let exponent: Result<i8, _> = exponent_str.parse();
             match exponent {
                 Result::Ok(exp) => {
-                    unit.metre *= exp;
-                    unit.second *= exp;
-                    unit.kilogram *= exp;
-                    unit.kelvin *= exp;
-                    unit.candela *= exp;
-                    unit.mole *= exp;
-                    unit.ampere *= exp;
-                    factor = factor.powi(exp as i32);
+                    unit = match unit.checked_powi(exp as i32) {
+                        Some(unit) => unit,
+                        None => { unit_error!("Unknown unit expression '{}' because the exponent '{}' is too large", text, exp); }
+                    };
+                    // decimal factors stay exact: cm3 is 1e-6m³ and g2 is 1e-6kg²
+                    let exponent = factor.log10().round() as i32;
+                    if factor == decimal_factor(exponent) {
+                        factor = decimal_factor(exponent * (exp as i32));
+                    }else{
+                        factor = factor.powi(exp as i32);
+                    }
                 }
                 Result::Err(e) => {
-                    panic!("Unknown unit expression '{}' due to unknown exponent '{}'. Parsing error: '{}'", text, exponent_str, e);
+                    unit_error!("Unknown unit expression '{}' due to unknown exponent '{}'. Parsing error: '{}'", text, exponent_str, e);
                 }
             }    
         }
 
-        (unit, factor, shift)
+        Ok((unit, factor, shift))
+    }
+
+    // the factor of an SI prefix
+    fn prefix_factor(prefix: &str) -> Option<f64> {
+        let factor = match prefix {
+            "Q"  => 1e30,
+            "R"  => 1e27,
+            "Y"  => 1e24,
+            "Z"  => 1e21,
+            "E"  => 1e18,
+            "P"  => 1e15,
+            "T"  => 1e12,
+            "G"  => 1e9,
+            "M"  => 1e6,
+            "k"  => 1e3,
+            "h"  => 1e2,
+            "da" => 1e1,
+            "d"  => 1.0/1e1,
+            "c"  => 1.0/1e2,
+            "m"  => 1.0/1e3,
+            "µ" | "μ" | "mu" | "mi" => 1.0/1e6, // warning: 'µ' and 'μ' are two different characters
+            "n"  => 1.0/1e9,
+            "p"  => 1.0/1e12,
+            "f"  => 1.0/1e15,
+            "a"  => 1.0/1e18,
+            "z"  => 1.0/1e21,
+            "y"  => 1.0/1e24,
+            "r"  => 1.0/1e27,
+            "q"  => 1.0/1e30,
+            _ => { return None; }
+        };
+        Some(factor)
+    }
+
+    // splits a name such as 'dam' or 'kJ' into a prefix and a unit
+    // two letters prefixes are tried first so that 'dam' is a decametre
+    fn prefixed_unit(name: &str) -> Option<(Unit, f64, f64)> {
+        let chars = name.graphemes(true).collect::<Vec<&str>>();
+        for prefix_len in [2, 1] {
+            if chars.len() > prefix_len {
+                let prefix = chars[..prefix_len].join("");
+                if let (Some(prefix_factor), Some((unit, factor, shift))) = (Unit::prefix_factor(&prefix), Unit::base_unit(&chars[prefix_len..].join(""))) {
+                    // powers of ten are combined through their exponents so that
+                    // mL is exactly 1e-6m³ and ng is exactly 1e-12kg
+                    let prefix_exponent = prefix_factor.log10().round() as i32;
+                    let unit_exponent = factor.log10().round() as i32;
+                    if factor == decimal_factor(unit_exponent) {
+                        return Some((unit, decimal_factor(prefix_exponent + unit_exponent), shift));
+                    }
+                    return Some((unit, prefix_factor * factor, shift));
+                }
+            }
+        }
+        None
+    }
+
+    // the unit, the factor to SI units and the shift of a unit name without prefixes
+    fn base_unit(name: &str) -> Option<(Unit, f64, f64)> {
+        let mut unit = Unit::unitless();
+        let mut factor = 1.0;
+        let mut shift = 0.0;
+        match name {
+            // SI base units
+            "m" => { unit.metre = 1; }
+            "s" => { unit.second = 1; }
+            "g" => { unit.kilogram = 1; factor = 1.0 / 1000.0; }
+            "K" => { unit.kelvin = 1; }
+            "cd" => { unit.candela = 1; }
+            "mol" => { unit.mole = 1; }
+            "A" => { unit.ampere = 1; }
+
+            // scales
+            "°C" => { unit.kelvin = 1; shift = -273.15; }
+
+            // unitless
+            "°" | "deg" => { factor = std::f64::consts::PI / 180.0; }
+            "%" => { factor = 0.01; }
+            "‰" => { factor = 0.001; }
+            "ppm" => { factor = 1e-6; }
+            "ppb" => { factor = 1e-9; }
+            "pi" | "π" => { factor = std::f64::consts::PI; }
+
+            // not SI
+            "L" => { unit.metre = 3; factor = 1.0 / 1000.0; }
+            "min" => { unit.second = 1; factor = 60.0; }
+            "h" => { unit.second = 1; factor = 3600.0; }
+            "AU" => { unit.metre = 1; factor = 149597870700.0; }
+            "ly" => { unit.metre = 1; factor = 9460730472580800.0; }
+            "pc" => { unit.metre = 1; factor = 149597870700.0 * 648000.0 / std::f64::consts::PI; } // not pico-coulomb
+            "bar" => { unit.kilogram = 1; unit.metre = -1; unit.second = -2; factor = 1e5; }
+            "atm" => { unit.kilogram = 1; unit.metre = -1; unit.second = -2; factor = 101325.0; }
+            "mmHg" => { unit.kilogram = 1; unit.metre = -1; unit.second = -2; factor = 133.322387415; } // not milli-mHg
+            "cal" => { unit.kilogram = 1; unit.metre = 2; unit.second = -2; factor = 4.184; } // not centi-al
+            "eV" => { factor = 1.602176565e-19; unit.kilogram = 1; unit.metre = 2; unit.second = -2; }
+
+            // derived units
+            "Hz" => { unit.second = -1; }
+            "N" => { unit.kilogram = 1; unit.metre = 1; unit.second = -2; }
+            "Pa" => { unit.kilogram = 1; unit.metre = -1; unit.second = -2; }
+            "J" => { unit.kilogram = 1; unit.metre = 2; unit.second = -2; }
+            "W" => { unit.kilogram = 1; unit.metre = 2; unit.second = -3; }
+            "C" => { unit.second = 1; unit.ampere = 1; }
+            "V" => { unit.kilogram = 1; unit.metre = 2; unit.second = -3; unit.ampere = -1; }
+            "F" => { unit.kilogram = -1; unit.metre = -2; unit.second = 4; unit.ampere = 2; }
+            "ohm" | "Ω" => { unit.kilogram = 1; unit.metre = 2; unit.second = -3; unit.ampere = -2; }
+            "S" => { unit.kilogram = -1; unit.metre = -2; unit.second = 3; unit.ampere = 2; }
+            "Wb" => { unit.kilogram = 1; unit.metre = 2; unit.second = -2; unit.ampere = -1; }
+            "Tesla" => { unit.kilogram = 1; unit.second = -2; unit.ampere = -1; }
+            "H" => { unit.kilogram = 1; unit.metre = 2; unit.second = -2; unit.ampere = -2; }
+            "lm" => { unit.candela = 1; }
+            "lx" => { unit.candela = 1; unit.metre = -2; }
+            "rad" | "sr" => { }
+            _ => { return None; }
+        }
+        Some((unit, factor, shift))
     }
 
     pub fn parse_unit_block(text: &str) -> (Unit, f64, f64) {
+        Unit::parse(text).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    // parses a full unit expression such as 'kg.m/s2' returning (unit, factor, shift)
+    pub fn parse(text: &str) -> Result<(Unit, f64, f64), UnitError> {
         let slash_split: Vec<&str> = text.split('/').collect();
         let prod: &str;
         let mut div= "";
@@ -215,7 +237,7 @@ impl Unit {
                 div = slash_split[1];
             }
             _ => {
-                panic!("Couldn't parse the unit block '{}' because more than one '/' where found", text);
+                unit_error!("Couldn't parse the unit block '{}' because more than one '/' where found", text);
             }
         }
 
@@ -225,42 +247,99 @@ impl Unit {
 
         let mut units_counter = 0;
 
-        for x in prod.split('.').map(|t| {
-            if t == "" { return (Unit::unitless(), 1.0, 0.0); }
+        // an empty text is no unit at all and '/s' is 1/s, but 'm.', 'm..s' or '/' miss a unit
+        if text.is_empty() {
+            return Ok((unit, factor, shift));
+        }
+        let prod_pieces: Vec<&str> = if prod.is_empty() && !div.is_empty() { vec![] } else { prod.split('.').collect() };
+        for t in prod_pieces {
+            if t.is_empty() { unit_error!("Couldn't parse the unit block '{}' because a unit is missing", text); }
             units_counter += 1;
-            crate::quantity::Unit::parse_single_unit(t)
-        }) {
-            unit = unit * x.0;
+            let x = Unit::try_parse_single_unit(t)?;
+            unit = match unit.checked_mul(&x.0) {
+                Some(unit) => unit,
+                None => { unit_error!("Couldn't parse the unit block '{}' because its exponents are too large", text); }
+            };
             factor *= x.1;
             shift += x.2;
         }
-        for x in div.split('.').map(|t| {
-            if t == "" { return (Unit::unitless(), 1.0, 0.0); }
-            units_counter += 1;
-            crate::quantity::Unit::parse_single_unit(t)
-        }) {
-            unit = unit / x.0;
-            factor /= x.1;
-            shift += x.2;
+        if slash_split.len() == 2 {
+            for t in div.split('.') {
+                if t.is_empty() { unit_error!("Couldn't parse the unit block '{}' because a unit is missing", text); }
+                units_counter += 1;
+                let x = Unit::try_parse_single_unit(t)?;
+                unit = match unit.checked_div(&x.0) {
+                    Some(unit) => unit,
+                    None => { unit_error!("Couldn't parse the unit block '{}' because its exponents are too large", text); }
+                };
+                factor /= x.1;
+                shift += x.2;
+            }
         }
 
         if shift != 0.0 && units_counter > 1 {
-            panic!("Shifted units cannot be composed with other units: '{text}'");
+            unit_error!("Shifted units cannot be composed with other units: '{text}'");
         }
 
-        (unit, factor, shift)
+        Ok((unit, factor, shift))
     }
 
-    pub fn powi(&self, i: i8) -> Unit {
-        Unit {
-            metre: self.metre * i,
-            second: self.second * i,
-            kilogram: self.kilogram * i,
-            kelvin: self.kelvin * i,
-            candela: self.candela * i,
-            mole: self.mole * i,
-            ampere: self.ampere * i,
+    // the unit raised to the power of n, None if some exponent overflows
+    pub fn checked_powi(&self, n: i32) -> Option<Unit> {
+        if self.is_unitless() { return Some(Unit::unitless()); }
+        let n = i8::try_from(n).ok()?;
+        Some(Unit {
+            metre: self.metre.checked_mul(n)?,
+            second: self.second.checked_mul(n)?,
+            kilogram: self.kilogram.checked_mul(n)?,
+            kelvin: self.kelvin.checked_mul(n)?,
+            candela: self.candela.checked_mul(n)?,
+            mole: self.mole.checked_mul(n)?,
+            ampere: self.ampere.checked_mul(n)?,
+        })
+    }
+
+    // like the product of two units but None if some exponent overflows
+    pub fn checked_mul(&self, rhs: &Unit) -> Option<Unit> {
+        Some(Unit {
+            mole: self.mole.checked_add(rhs.mole)?,
+            metre: self.metre.checked_add(rhs.metre)?,
+            second: self.second.checked_add(rhs.second)?,
+            kilogram: self.kilogram.checked_add(rhs.kilogram)?,
+            kelvin: self.kelvin.checked_add(rhs.kelvin)?,
+            ampere: self.ampere.checked_add(rhs.ampere)?,
+            candela: self.candela.checked_add(rhs.candela)?,
+        })
+    }
+
+    // like the quotient of two units but None if some exponent overflows
+    pub fn checked_div(&self, rhs: &Unit) -> Option<Unit> {
+        Some(Unit {
+            mole: self.mole.checked_sub(rhs.mole)?,
+            metre: self.metre.checked_sub(rhs.metre)?,
+            second: self.second.checked_sub(rhs.second)?,
+            kilogram: self.kilogram.checked_sub(rhs.kilogram)?,
+            kelvin: self.kelvin.checked_sub(rhs.kelvin)?,
+            ampere: self.ampere.checked_sub(rhs.ampere)?,
+            candela: self.candela.checked_sub(rhs.candela)?,
+        })
+    }
+
+    // divides every exponent by n, None if some exponent is not divisible
+    pub fn root(&self, n: i8) -> Option<Unit> {
+        let exponents = [self.metre, self.second, self.kilogram, self.kelvin, self.candela, self.mole, self.ampere];
+        if n == 0 || exponents.iter().any(|e| e % n != 0) {
+            return None;
         }
+        Some(Unit {
+            metre: self.metre / n,
+            second: self.second / n,
+            kilogram: self.kilogram / n,
+            kelvin: self.kelvin / n,
+            candela: self.candela / n,
+            mole: self.mole / n,
+            ampere: self.ampere / n,
+        })
     }
 
     pub fn taxi_norm(&self) -> i8 {
@@ -395,9 +474,9 @@ impl std::fmt::Display for Unit {
 }
 
 macro_rules! disp_unit {
-    ($selff:ident, $string:ident, $first:ident, $counter:ident, $field: ident, $name:expr) => {
+    ($selff:ident, $string:ident, $counter:ident, $field: ident, $name:expr) => {
         if $selff.$field != 0 {
-            if $first { $first = false; }else{ $string.push('.'); }
+            if !$string.is_empty() { $string.push('.'); }
             let mut n: String = if $selff.$field != 1 { $selff.$field.to_string() }else{ String::new() };
             n = n.chars().map(|c: char| {
                 return match c {
@@ -425,29 +504,28 @@ macro_rules! disp_unit {
 impl std::fmt::Display for ComposedUnit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut string = String::new();
-        let mut first = true;
         let mut counter: u8 = 0;
 
-        disp_unit!(self, string, first, counter, N, "N");
-        disp_unit!(self, string, first, counter, Pa, "Pa");
-        disp_unit!(self, string, first, counter, J, "J");
-        disp_unit!(self, string, first, counter, W, "W");
-        disp_unit!(self, string, first, counter, C, "C");
-        disp_unit!(self, string, first, counter, V, "V");
-        disp_unit!(self, string, first, counter, F, "F");
-        disp_unit!(self, string, first, counter, ohm, "Ω");
-        disp_unit!(self, string, first, counter, S, "S");
-        disp_unit!(self, string, first, counter, Wb, "Wb");
-        disp_unit!(self, string, first, counter, Tesla, "Tesla");
-        disp_unit!(self, string, first, counter, H, "H");
-        disp_unit!(self, string, first, counter, lx, "lx");
-        disp_unit!(self, string, first, counter, kilogram, "kg");
-        disp_unit!(self, string, first, counter, ampere, "A");
-        disp_unit!(self, string, first, counter, mole, "mol");
-        disp_unit!(self, string, first, counter, metre, "m");
-        disp_unit!(self, string, first, counter, second, "s");
-        disp_unit!(self, string, first, counter, kelvin, "K");
-        disp_unit!(self, string, first, counter, candela, "cd");
+        disp_unit!(self, string, counter, N, "N");
+        disp_unit!(self, string, counter, Pa, "Pa");
+        disp_unit!(self, string, counter, J, "J");
+        disp_unit!(self, string, counter, W, "W");
+        disp_unit!(self, string, counter, C, "C");
+        disp_unit!(self, string, counter, V, "V");
+        disp_unit!(self, string, counter, F, "F");
+        disp_unit!(self, string, counter, ohm, "Ω");
+        disp_unit!(self, string, counter, S, "S");
+        disp_unit!(self, string, counter, Wb, "Wb");
+        disp_unit!(self, string, counter, Tesla, "Tesla");
+        disp_unit!(self, string, counter, H, "H");
+        disp_unit!(self, string, counter, lx, "lx");
+        disp_unit!(self, string, counter, kilogram, "kg");
+        disp_unit!(self, string, counter, ampere, "A");
+        disp_unit!(self, string, counter, mole, "mol");
+        disp_unit!(self, string, counter, metre, "m");
+        disp_unit!(self, string, counter, second, "s");
+        disp_unit!(self, string, counter, kelvin, "K");
+        disp_unit!(self, string, counter, candela, "cd");
 
         if counter <= 1 {
             write!(f, "{}", string)
@@ -484,6 +562,28 @@ impl ops::Div<Unit> for Unit {
 #[inline]
 fn squared(x: f64) -> f64 { x*x }
 
+// Builds f(z) for an analytic function f given f(z) = re + i im and f'(z) = dre + i dim.
+// By the Cauchy-Riemann equations dRe(f)/da = dIm(f)/db = dre and dIm(f)/da = -dRe(f)/db = dim,
+// where z = a + ib, so the variances are propagated to first order.
+fn analytic(z: &Quantity, re: f64, im: f64, dre: f64, dim: f64, unit: Unit) -> Quantity {
+    Quantity {
+        re,
+        im,
+        vre: if z.vre == 0.0 && z.vim == 0.0 { 0.0 } else { squared(dre)*z.vre + squared(dim)*z.vim },
+        vim: if z.vre == 0.0 && z.vim == 0.0 { 0.0 } else { squared(dim)*z.vre + squared(dre)*z.vim },
+        unit,
+    }
+}
+
+// argument of a + ib in (-π, π], negative reals have argument π even when b is -0.0
+fn principal_arg(re: f64, im: f64) -> f64 {
+    if im == 0.0 {
+        if re >= 0.0 { 0.0 } else { std::f64::consts::PI }
+    }else{
+        im.atan2(re)
+    }
+}
+
 // Quantity with a value an uncertainty and it's unit
 #[derive(Debug, Clone, PartialEq)]
 pub struct Quantity {
@@ -500,9 +600,9 @@ impl PartialEq<f64> for Quantity {
     }
 }
 
-impl Into<Quantity> for f64 {
-    fn into(self) -> Quantity {
-        Quantity { re: self, im: 0.0, vre: 0.0, vim: 0.0, unit: Unit::unitless() }
+impl From<f64> for Quantity {
+    fn from(val: f64) -> Self {
+        Quantity { re: val, im: 0.0, vre: 0.0, vim: 0.0, unit: Unit::unitless() }
     }
 }
 
@@ -564,19 +664,23 @@ impl ops::Div<Quantity> for Quantity {
         let denom4 = denom2*denom2;
         let re = a*c + b*d;
         let im = b*c - a*d;
+        // exact values stay exact, even when the formulas below give 0/0 for x/0
+        let exact = va == 0.0 && vb == 0.0 && vc == 0.0 && vd == 0.0;
         Quantity {
-            re:  re / denom,
-            im:  im / denom,
-            vre: 
+            // dividing by a real number directly gives the IEEE results for x/0,
+            // and a real number stays real rather than getting the imaginary part 0/0
+            re:  if d == 0.0 { a / c } else { re / denom },
+            im:  if d == 0.0 { if b == 0.0 { 0.0 } else { b / c } } else { im / denom },
+            vre: if exact { 0.0 } else {
                 c*c*va/denom2 + 
                 d*d*vb/denom2 + 
                 squared(a*denom - 2.0*c*re)*vc/denom4 + 
-                squared(b*denom - 2.0*d*re)*vd/denom4,
-            vim: 
+                squared(b*denom - 2.0*d*re)*vd/denom4 },
+            vim: if exact { 0.0 } else {
                 d*d*va/denom2 +
                 c*c*vb/denom2 +
                 squared(b*denom - 2.0*c*im)*vc/denom4 +
-                squared(a*denom - 2.0*d*im)*vd/denom4,
+                squared(a*denom - 2.0*d*im)*vd/denom4 },
             unit: self.unit / rhs.unit,
         }
     }
@@ -603,25 +707,60 @@ impl ops::Mul<f64> for Quantity {
 }
 
 impl Quantity {
-    pub fn is_real(&self) -> bool {
-        self.im == 0.0 && self.vim == 0.0
+    // real unitless quantity without uncertainty
+    pub fn real(value: f64) -> Quantity {
+        Quantity { re: value, im: 0.0, vre: 0.0, vim: 0.0, unit: Unit::unitless() }
+    }
+
+    // real unitless quantity with standard deviation sigma
+    pub fn with_uncertainty(value: f64, sigma: f64) -> Quantity {
+        Quantity { re: value, im: 0.0, vre: sigma*sigma, vim: 0.0, unit: Unit::unitless() }
+    }
+
+    // real quantity without uncertainty, value is expressed in SI units
+    pub fn with_unit(value: f64, unit: Unit) -> Quantity {
+        Quantity { re: value, im: 0.0, vre: 0.0, vim: 0.0, unit }
+    }
+
+    // standard deviation of the real part
+    pub fn sigma_re(&self) -> f64 {
+        self.vre.sqrt()
+    }
+
+    // standard deviation of the imaginary part
+    pub fn sigma_im(&self) -> f64 {
+        self.vim.sqrt()
+    }
+
+    // real, without uncertainty and with an integer value
+    pub fn is_integer(&self) -> bool {
+        self.im == 0.0 && self.vre == 0.0 && self.vim == 0.0 && self.re.is_finite() && self.re == self.re.floor()
     }
 
-    pub fn is_imaginary(&self) -> bool {
-        self.re == 0.0 && self.vre == 0.0 && (self.im != 0.0 && self.vim != 0.0)
+    // interprets the quantity as a 1-based index, negative values count from the end
+    pub fn as_index(&self) -> Result<i64, String> {
+        if self.is_integer() && self.unit.is_unitless() && self.re != 0.0 && self.re.abs() < 9.0e18 {
+            Ok(self.re as i64)
+        }else{
+            Err(format!("Matrix indices are 1-based, so only pure, unitless, integer, non zero values are allowed when indexing a matrix (negative values count from the end) but '{self}' was found."))
+        }
+    }
+
+    pub fn is_real(&self) -> bool {
+        self.im == 0.0 && self.vim == 0.0
     }
 
     pub fn from_value_decorator(val: f64, dec: &String) -> Quantity {
         let mut unit = Unit::unitless();
 
-        if dec == "" { return Quantity { re: val, im: 0.0, vre: 0.0, vim: 0.0, unit: unit }; }
-        else if dec == "i" || dec == "j" { return Quantity { re: 0.0, im: val, vre: 0.0, vim: 0.0, unit: unit }; }
+        if dec.is_empty() { return Quantity { re: val, im: 0.0, vre: 0.0, vim: 0.0, unit }; }
+        else if dec == "i" || dec == "j" { return Quantity { re: 0.0, im: val, vre: 0.0, vim: 0.0, unit }; }
 
         let factor;
         let shift;
         (unit, factor, shift) = Unit::parse_single_unit(dec);
 
-        Quantity { re: (val + shift) * factor, im: 0.0, vre: 0.0, vim: 0.0, unit: unit }
+        Quantity { re: val * factor - shift, im: 0.0, vre: 0.0, vim: 0.0, unit }
     }
 
     pub fn sin(&self) -> Quantity {
@@ -671,8 +810,212 @@ impl Quantity {
     }
 
     pub fn ln(&self) -> Quantity {
-        // ln(z) = ln(A expiθ) = ln(A) + iθ
-        todo!();
+        // ln(z) = ln(A expiθ) = ln(A) + iθ with θ in (-π, π]
+        let r2 = self.re*self.re + self.im*self.im;
+        let re = 0.5 * r2.ln();
+        let im = principal_arg(self.re, self.im);
+        // d/dz ln(z) = 1/z = (a - ib) / (a² + b²)
+        analytic(self, re, im, self.re / r2, -self.im / r2, Unit::unitless())
+    }
+
+    // z^n for an integer n, units are raised to the n-th power
+    pub fn powi(&self, n: i32) -> Quantity {
+        let unit = match self.unit.checked_powi(n) {
+            Some(u) => u,
+            None => { panic!("Overflow happened while raising the units of '{}' to the power of {n}.", self); }
+        };
+        if n == 0 {
+            // 0^0 == 1
+            return Quantity { re: 1.0, im: 0.0, vre: 0.0, vim: 0.0, unit };
+        }
+        if n == 1 {
+            return self.clone();
+        }
+        let (re, im) = if self.im == 0.0 {
+            // integer bases are raised exactly as long as the result fits an i64
+            let exact = if self.re == self.re.trunc() && self.re.abs() < 9007199254740992.0 {
+                i64::checked_pow(self.re as i64, n.unsigned_abs()).map(|p| p as f64)
+            }else{
+                None
+            };
+            match exact {
+                Some(p) => (if n > 0 { p } else { 1.0 / p }, 0.0),
+                // f64::powi never wraps around: it gives inf on overflow and 0^-n == inf
+                None => (self.re.powi(n), 0.0),
+            }
+        }else{
+            // exponentiation by squaring keeps gaussian integers exact
+            let (mut re, mut im) = (1.0, 0.0);
+            let (mut base_re, mut base_im) = (self.re, self.im);
+            let mut k = n.unsigned_abs();
+            while k > 0 {
+                if k % 2 == 1 {
+                    (re, im) = (re*base_re - im*base_im, re*base_im + im*base_re);
+                }
+                (base_re, base_im) = (base_re*base_re - base_im*base_im, 2.0*base_re*base_im);
+                k /= 2;
+            }
+            if n > 0 { (re, im) } else { let r2 = re*re + im*im; (re / r2, -im / r2) }
+        };
+        // d/dz z^n = n z^n / z
+        let r2 = self.re*self.re + self.im*self.im;
+        let dre = n as f64 * (re*self.re + im*self.im) / r2;
+        let dim = n as f64 * (im*self.re - re*self.im) / r2;
+        analytic(self, re, im, dre, dim, unit)
+    }
+
+    // z^w, non integer exponents require unitless bases
+    pub fn pow(&self, exponent: &Quantity) -> Quantity {
+        if !exponent.unit.is_unitless() {
+            panic!("Exponents must be unitless quantities but '{}' was found.", exponent);
+        }
+        if exponent.is_real() && exponent.vre == 0.0 && exponent.re == exponent.re.floor() && exponent.re.abs() <= i32::MAX as f64 {
+            return self.powi(exponent.re as i32);
+        }
+        if exponent.is_real() && exponent.vre == 0.0 && exponent.re == 0.5 {
+            return self.nthroot(2);
+        }
+        if !self.unit.is_unitless() {
+            panic!("Only unitless quantities can be raised to the non integer power '{}' but '{}' was found.", exponent, self);
+        }
+        if self.re == 0.0 && self.im == 0.0 {
+            // 0^w is 0 for positive exponents and inf for negative ones
+            let re = if exponent.re > 0.0 { 0.0 } else { f64::INFINITY };
+            return Quantity { re, im: 0.0, vre: 0.0, vim: 0.0, unit: Unit::unitless() };
+        }
+        let ln = self.clone().value().ln();
+        let (re, im) = if self.im == 0.0 && self.re > 0.0 && exponent.is_real() {
+            (self.re.powf(exponent.re), 0.0)
+        }else{
+            // z^w = e^(w ln z), negative bases give the principal complex value
+            let x = exponent.re*ln.re - exponent.im*ln.im;
+            let y = exponent.re*ln.im + exponent.im*ln.re;
+            let ex = x.exp();
+            (ex * y.cos(), ex * y.sin())
+        };
+        // d/dz z^w = w z^w / z
+        let r2 = self.re*self.re + self.im*self.im;
+        let wf = (exponent.re*re - exponent.im*im, exponent.re*im + exponent.im*re);
+        let from_base = analytic(self, re, im, (wf.0*self.re + wf.1*self.im) / r2, (wf.1*self.re - wf.0*self.im) / r2, Unit::unitless());
+        // d/dw z^w = z^w ln z
+        let from_exponent = analytic(exponent, re, im, re*ln.re - im*ln.im, re*ln.im + im*ln.re, Unit::unitless());
+        Quantity {
+            re,
+            im,
+            vre: from_base.vre + from_exponent.vre,
+            vim: from_base.vim + from_exponent.vim,
+            unit: Unit::unitless(),
+        }
+    }
+
+    // principal n-th root, n must be a positive integer dividing every unit exponent
+    pub fn nthroot(&self, n: u32) -> Quantity {
+        if n == 0 { panic!("Cannot take the root of index 0 of '{}'.", self); }
+        let unit = match i8::try_from(n).ok().and_then(|n| self.unit.root(n)) {
+            Some(u) => u,
+            None => { panic!("Cannot take the root of index {n} of '{}' because its unit exponents are not divisible by {n}.", self); }
+        };
+        let (re, im) = if self.im == 0.0 && self.re >= 0.0 {
+            // real branch, exact for perfect squares and cubes
+            (match n { 1 => self.re, 2 => self.re.sqrt(), 3 => self.re.cbrt(), _ => self.re.powf(1.0 / n as f64) }, 0.0)
+        }else if self.im == 0.0 && n == 2 {
+            // sqrt(-x) = i sqrt(x), avoids the rounding error of cos(π/2)
+            (0.0, (-self.re).sqrt())
+        }else{
+            // z^(1/n) = |z|^(1/n) e^(iθ/n) with θ in (-π, π]
+            let r = (self.re*self.re + self.im*self.im).sqrt().powf(1.0 / n as f64);
+            let theta = principal_arg(self.re, self.im) / n as f64;
+            (r * theta.cos(), r * theta.sin())
+        };
+        // d/dz z^(1/n) = z^(1/n) / (n z)
+        let denom = n as f64 * (self.re*self.re + self.im*self.im);
+        let dre = (re*self.re + im*self.im) / denom;
+        let dim = (im*self.re - re*self.im) / denom;
+        analytic(self, re, im, dre, dim, unit)
+    }
+
+    // assumes real quantities with the same units
+    pub fn hypot(&self, other: &Quantity) -> Quantity {
+        let h = self.re.hypot(other.re);
+        // dh/da = a/h, dh/db = b/h, the ratios are squared so that huge values do not overflow
+        let vre = if h == 0.0 { 0.0 } else { squared(self.re / h)*self.vre + squared(other.re / h)*other.vre };
+        Quantity { re: h, im: 0.0, vre, vim: 0.0, unit: self.unit.clone() }
+    }
+
+    // floor, ceil and round act on the real and imaginary parts separately,
+    // the values are in SI units and the results have no uncertainty
+    pub fn floor(&self) -> Quantity {
+        Quantity { re: self.re.floor(), im: self.im.floor(), vre: 0.0, vim: 0.0, unit: self.unit.clone() }
+    }
+
+    pub fn ceil(&self) -> Quantity {
+        Quantity { re: self.re.ceil(), im: self.im.ceil(), vre: 0.0, vim: 0.0, unit: self.unit.clone() }
+    }
+
+    pub fn round(&self) -> Quantity {
+        Quantity { re: self.re.round(), im: self.im.round(), vre: 0.0, vim: 0.0, unit: self.unit.clone() }
+    }
+
+    // assumes real quantities with the same units and a non zero step
+    pub fn round_to(&self, step: &Quantity) -> Quantity {
+        let steps = (self.re / step.re).round();
+        // 73 * 0.1 is 7.300000000000001 but 73 / 10 is 7.3
+        let inverse = 1.0 / step.re;
+        let re = if inverse == inverse.round() { steps / inverse } else { steps * step.re };
+        Quantity { re, im: 0.0, vre: 0.0, vim: 0.0, unit: self.unit.clone() }
+    }
+
+    // rounds the uncertainties to the given significant figures, as lab results are reported,
+    // and the values to the last decimal place of their uncertainty: 12.34 ± 0.46 -> 12.3 ± 0.5
+    pub fn round_uncertainty(&self, significant_figures: u32) -> Quantity {
+        let (re, sre) = round_to_uncertainty(self.re, self.vre.sqrt(), significant_figures);
+        let (im, sim) = round_to_uncertainty(self.im, self.vim.sqrt(), significant_figures);
+        Quantity { re, im, vre: sre*sre, vim: sim*sim, unit: self.unit.clone() }
+    }
+
+    // evaluates the polynomial with the given coefficients, from the highest degree, at self
+    // the uncertainty of self is propagated through the derivative of the polynomial
+    pub fn polyval(&self, coefficients: &[Quantity]) -> Quantity {
+        let x = self.clone().value();
+        // horner's method for p(x) and p'(x)
+        let mut p = coefficients[0].clone().value();
+        let mut dp = Quantity::with_unit(0.0, p.unit.clone() / x.unit.clone());
+        for c in coefficients[1..].iter() {
+            dp = dp * x.clone() + p.clone();
+            p = p * x.clone();
+            if p.unit != c.unit {
+                panic!("Cannot evaluate the polynomial because '{}' cannot be added to '{}'.", c, p);
+            }
+            p = p + c.clone().value();
+        }
+        let mut res = analytic(self, p.re, p.im, dp.re, dp.im, p.unit);
+        // each coefficient contributes its own uncertainty times x^k
+        let mut power = Quantity::with_unit(1.0, Unit::unitless());
+        for c in coefficients.iter().rev() {
+            let term = c.clone() * power.clone();
+            res.vre += term.vre;
+            res.vim += term.vim;
+            power = power * x.clone();
+        }
+        res
+    }
+
+    // roots of a x² + b x + c, assuming a != 0 and consistent units, the one with +sqrt first
+    pub fn quadratic_roots(a: &Quantity, b: &Quantity, c: &Quantity) -> (Quantity, Quantity) {
+        let four = Quantity::with_unit(4.0, Unit::unitless());
+        let two = Quantity::with_unit(2.0, Unit::unitless());
+        let discriminant = b.clone() * b.clone() - four * a.clone() * c.clone();
+        let root = discriminant.nthroot(2);
+        // q = -(b ± sqrt(Δ)) / 2 with the sign of b avoids the cancellation of -b + sqrt(Δ)
+        let same_sign = b.re*root.re + b.im*root.im >= 0.0;
+        let q = if same_sign { -(b.clone() + root) / two } else { -(b.clone() - root) / two };
+        if q.re == 0.0 && q.im == 0.0 {
+            // b = 0 and c = 0
+            let zero = Quantity { re: 0.0, im: 0.0, vre: 0.0, vim: 0.0, unit: c.unit.clone() / b.unit.clone() };
+            return (zero.clone(), zero);
+        }
+        let (near, far) = (q.clone() / a.clone(), c.clone() / q);
+        if same_sign { (far, near) } else { (near, far) }
     }
 
     // assumes real quantities
@@ -717,46 +1060,87 @@ impl Quantity {
         Quantity { 
             re: (self.re*self.re + self.im*self.im).sqrt(), 
             im: 0.0, 
-            vre: ( self.vre * self.re * self.re + self.vim * self.im * self.im ) / (self.re*self.re + self.im*self.im) , 
+            // zero is a common matrix cell, there the variance is taken from the real part
+            vre: if self.re == 0.0 && self.im == 0.0 { self.vre } else { ( self.vre * self.re * self.re + self.vim * self.im * self.im ) / (self.re*self.re + self.im*self.im) }, 
             vim: 0.0, 
             unit: self.unit 
         }
     }
 
+    // principal argument in (-π, π], the branch cut of ln and sqrt lies on the negative reals
     pub fn arg(self) -> Quantity {
-        let datan2 = 1.0 / squared(1.0 + self.im*self.im/(self.re*self.re));
-        Quantity { 
-            re: self.im.atan2(self.re),
-            im: 0.0, 
-            vre: self.vre * datan2 * (-1.0) * self.im * self.im / squared(self.re*self.re) + self.vim * datan2 / self.re / self.re, 
-            vim: 0.0, 
-            unit: Unit::unitless() 
-        }
+        let theta = principal_arg(self.re, self.im);
+        self.with_argument(theta)
+    }
+
+    // argument in [0, 2π), measured counterclockwise from the positive reals
+    pub fn arg_positive(self) -> Quantity {
+        let theta = principal_arg(self.re, self.im);
+        self.with_argument(if theta < 0.0 { theta + 2.0 * std::f64::consts::PI } else { theta })
+    }
+
+    fn with_argument(self, theta: f64) -> Quantity {
+        // dθ/da = -b / (a² + b²), dθ/db = a / (a² + b²)
+        let r2 = self.re*self.re + self.im*self.im;
+        let vre = if r2 == 0.0 { 0.0 } else { (squared(self.im)*self.vre + squared(self.re)*self.vim) / squared(r2) };
+        Quantity { re: theta, im: 0.0, vre, vim: 0.0, unit: Unit::unitless() }
     }
 }
 
 fn powi(base: i32, exponent: i32) -> f64 {
     if exponent >= 0 {
         i32::checked_pow(base, exponent as u32)
-        .expect(&format!("Overflow happened while raising {base} to the power of {exponent}.")) as f64
+        .unwrap_or_else(|| panic!("Overflow happened while raising {base} to the power of {exponent}.")) as f64
     }else{
         1.0 / (
             i32::checked_pow(base, (-exponent) as u32)
-            .expect(&format!("Overflow happened while raising {base} to the power of {exponent}.")) as f64
+            .unwrap_or_else(|| panic!("Overflow happened while raising {base} to the power of {exponent}.")) as f64
         )
     }
 }
 
+// rounds x to the decimal place of sx rounded to the given significant figures
+fn round_to_uncertainty(x: f64, sx: f64, significant_figures: u32) -> (f64, f64) {
+    if sx == 0.0 || !sx.is_finite() || significant_figures == 0 { return (x, sx); }
+    // multiplying by a power of ten is exact only for non negative exponents
+    let round_at = |v: f64, decimals: i32| -> f64 {
+        if decimals >= 0 {
+            let p = powi(10, decimals);
+            (v * p).round() / p
+        }else{
+            let p = powi(10, -decimals);
+            (v / p).round() * p
+        }
+    };
+    let mut decimals = significant_figures as i32 - 1 - sx.abs().log10().floor() as i32;
+    let mut rounded = round_at(sx, decimals);
+    if rounded.abs().log10().floor() as i32 > significant_figures as i32 - 1 - decimals {
+        // 0.96 -> 1.0 gains a digit, which is one significant figure too many
+        decimals -= 1;
+        rounded = round_at(sx, decimals);
+    }
+    (round_at(x, decimals), rounded)
+}
+
 fn number_to_text(x: f64, sx: f64, force_parenthesis: bool) -> String {
-    let og: i32 = x.abs().log10().floor() as i32;
-    let ogs: i32 = sx.abs().log10().floor() as i32;
+    // inf, -inf and NaN have no order of magnitude
+    if !x.is_finite() || !sx.is_finite() {
+        if force_parenthesis {
+            return format!("({x} ± {sx})");
+        }else{
+            return format!("{x} ± {sx}");
+        }
+    }
+    // the order of magnitude of zero is taken from the other number
+    let og: i32 = if x != 0.0 { x.abs().log10().floor() as i32 } else if sx != 0.0 { sx.abs().log10().floor() as i32 } else { 0 };
+    let ogs: i32 = if sx != 0.0 { sx.abs().log10().floor() as i32 } else { og };
     let common_og = i32::max(og, ogs);
     let powi_common_og = powi(10, common_og);
     let cifre = i32::max(0, common_og - ogs);
     let mantissa_x = format!("{0:.1$}", x / powi_common_og, cifre as usize);
     let mantissa_sx = format!("{0:.1$}", sx / powi_common_og, cifre as usize);
     let common_og_str: String = format!("{common_og}").chars().map(|c: char| {
-        return match c {
+        match c {
             '0' => '⁰', '1' => '¹',
             '2' => '²', '3' => '³',
             '4' => '⁴', '5' => '⁵',
@@ -768,12 +1152,12 @@ fn number_to_text(x: f64, sx: f64, force_parenthesis: bool) -> String {
     }).collect();
     if common_og == 0 {
         if force_parenthesis {
-            return format!("({mantissa_x} ± {mantissa_sx})");
+            format!("({mantissa_x} ± {mantissa_sx})")
         }else{
-            return format!("{mantissa_x} ± {mantissa_sx}");
+            format!("{mantissa_x} ± {mantissa_sx}")
         }
     }else{
-        return format!("({mantissa_x} ± {mantissa_sx})×10{common_og_str}");
+        format!("({mantissa_x} ± {mantissa_sx})×10{common_og_str}")
     }
 }
 
@@ -813,7 +1197,23 @@ impl std::fmt::Display for Quantity {
 
 impl Quantity {
     pub fn to_text(&self, unit_str: String) -> String {
-        let (unit, factor, shift) = if unit_str != "" {
+        self.to_text_with_precision(unit_str, None)
+    }
+
+    // like to_text but, when a precision is given, values and uncertainties
+    // are written with that number of decimals
+    pub fn to_text_with_precision(&self, unit_str: String, precision: Option<usize>) -> String {
+        let plain = |x: f64| -> String {
+            match precision { Some(p) => format!("{x:.p$}"), None => format!("{x}") }
+        };
+        let uncertain = |x: f64, sx: f64, force_parenthesis: bool| -> String {
+            match precision {
+                Some(p) if force_parenthesis => format!("({x:.p$} ± {sx:.p$})"),
+                Some(p) => format!("{x:.p$} ± {sx:.p$}"),
+                None => number_to_text(x, sx, force_parenthesis),
+            }
+        };
+        let (unit, factor, shift) = if !unit_str.is_empty() {
             Unit::parse_unit_block(&unit_str)
         } else {
             (Unit::unitless(), 1.0, 0.0)
@@ -829,53 +1229,271 @@ impl Quantity {
             im: self.im / factor, 
             vre: self.vre / factor / factor, 
             vim: self.vim / factor / factor, 
-            unit: unit,
+            unit,
         };
 
         if values.is_real() {
             if self.unit.is_unitless() {
                 if values.vre == 0.0 {
-                    return format!("{}", values.re);
+                    plain(values.re)
                 }else{
-                    return format!("{}", number_to_text(values.re, values.vre.sqrt(), false));
+                    uncertain(values.re, values.vre.sqrt(), false)
                 }
             }else{
                 if values.vre == 0.0 {
-                    if unit_str != "" {
-                        return format!("{}{}", values.re, unit_str);
+                    if !unit_str.is_empty() {
+                        format!("{}{}", plain(values.re), unit_str)
                     }else{
-                        return format!("{}{}", values.re, self.unit);
+                        format!("{}{}", plain(values.re), self.unit)
                     }
                 }else{
-                    if unit_str != "" {
-                        return format!("{}{}", number_to_text(values.re, values.vre.sqrt(), true), unit_str);
+                    if !unit_str.is_empty() {
+                        format!("{}{}", uncertain(values.re, values.vre.sqrt(), true), unit_str)
                     }else{
-                        return format!("{}{}", number_to_text(values.re, values.vre.sqrt(), true), self.unit);
+                        format!("{}{}", uncertain(values.re, values.vre.sqrt(), true), self.unit)
                     }
                 }
             }
         }else{
             if self.unit.is_unitless() {
                 if values.vre == 0.0 && values.vim == 0.0 {
-                    return format!("{} + {}i", values.re, values.im);
+                    format!("{} + {}i", plain(values.re), plain(values.im))
                 }else{
-                    return format!("{} + i{}", number_to_text(values.re, values.vre.sqrt(), true), number_to_text(values.im, values.vim.sqrt(), false));
+                    format!("{} + i{}", uncertain(values.re, values.vre.sqrt(), true), uncertain(values.im, values.vim.sqrt(), false))
                 }
             }else{
                 if values.vre == 0.0 && values.vim == 0.0 {
-                    if unit_str != "" {
-                        return format!("({} + {}i){}", values.re, values.im, unit_str);
+                    if !unit_str.is_empty() {
+                        format!("({} + {}i){}", plain(values.re), plain(values.im), unit_str)
                     }else{
-                        return format!("({} + {}i){}", values.re, values.im, self.unit);
+                        format!("({} + {}i){}", plain(values.re), plain(values.im), self.unit)
                     }
                 }else{
-                    if unit_str != "" {
-                        return format!("{}{} + i{}{}", number_to_text(values.re, values.vre.sqrt(), true), unit_str, number_to_text(values.im, values.vim.sqrt(), true), unit_str);
+                    if !unit_str.is_empty() {
+                        format!("{}{} + i{}{}", uncertain(values.re, values.vre.sqrt(), true), unit_str, uncertain(values.im, values.vim.sqrt(), true), unit_str)
                     }else{
-                        return format!("{}{} + i{}{}", number_to_text(values.re, values.vre.sqrt(), true), self.unit, number_to_text(values.im, values.vim.sqrt(), true), self.unit);
+                        format!("{}{} + i{}{}", uncertain(values.re, values.vre.sqrt(), true), self.unit, uncertain(values.im, values.vim.sqrt(), true), self.unit)
                     }
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(text: &str) -> Unit {
+        Unit::parse(text).unwrap().0
+    }
+
+    #[test]
+    fn constructors_fill_the_fields_and_display() {
+        let q = Quantity::real(2.5);
+        assert_eq!((q.re, q.im, q.vre, q.vim), (2.5, 0.0, 0.0, 0.0));
+        assert!(q.unit.is_unitless());
+        assert_eq!(format!("{q}"), "2.5");
+
+        let q = Quantity::with_uncertainty(2.0, 0.1);
+        assert_eq!((q.sigma_re(), q.sigma_im()), (0.1, 0.0));
+        assert_eq!(format!("{q}"), "2.0 ± 0.1");
+        // the displayed text evaluates back to the same quantity
+        match crate::ast::eval::Evaluator::eval_str(&format!("{q}")) {
+            Ok(crate::ast::eval::RValue::Number(back)) => assert_eq!(back, q),
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        let q = Quantity::with_unit(3.0, unit("m/s"));
+        assert_eq!(q.unit, unit("m/s"));
+        assert_eq!(format!("{q}"), "3|m.s⁻¹|");
+    }
+
+    #[test]
+    fn parse_accepts_unit_expressions() {
+        let newton = Unit { kilogram: 1, metre: 1, second: -2, ..Unit::unitless() };
+        assert_eq!(Unit::parse("kg.m/s2"), Ok((newton, 1.0, 0.0)));
+        assert_eq!(Unit::parse("km"), Ok((Unit { metre: 1, ..Unit::unitless() }, 1000.0, 0.0)));
+        assert_eq!(Unit::parse("/s"), Ok((Unit { second: -1, ..Unit::unitless() }, 1.0, 0.0)));
+        assert_eq!(Unit::parse(""), Ok((Unit::unitless(), 1.0, 0.0)));
+        assert_eq!(Unit::parse("°C"), Ok((Unit { kelvin: 1, ..Unit::unitless() }, 1.0, -273.15)));
+    }
+
+    #[test]
+    fn parse_reports_invalid_unit_expressions() {
+        let message = |text: &str| Unit::parse(text).unwrap_err().message;
+        assert_eq!(message("xyz"), "Unknown unit expression 'xyz' due to unknown unit 'xyz'");
+        assert_eq!(message("a/b/c"), "Couldn't parse the unit block 'a/b/c' because more than one '/' where found");
+        assert_eq!(message("°C.m"), "Shifted units cannot be composed with other units: '°C.m'");
+        // empty pieces
+        for text in ["/", "m/", "m.", "m..s", ".s"] {
+            assert_eq!(message(text), format!("Couldn't parse the unit block '{text}' because a unit is missing"));
+        }
+        // exponents out of the i8 range
+        for text in ["m100.m100", "m127/m-127"] {
+            assert_eq!(message(text), format!("Couldn't parse the unit block '{text}' because its exponents are too large"));
+        }
+        assert_eq!(message("N100"), "Unknown unit expression 'N100' because the exponent '100' is too large");
+    }
+
+    #[test]
+    #[should_panic(expected = "due to unknown unit 'xyz'")]
+    fn parse_unit_block_panics_on_invalid_units() {
+        Unit::parse_unit_block("xyz");
+    }
+
+    #[test]
+    fn pow_handles_the_special_cases() {
+        let pow = |base: f64, exponent: f64| Quantity::real(base).pow(&Quantity::real(exponent));
+        assert_eq!(pow(0.0, 0.0), 1.0);
+        assert_eq!(pow(0.0, -1.0), f64::INFINITY);
+        assert_eq!(pow(0.0, -1.5), f64::INFINITY);
+        assert_eq!(pow(0.0, 2.5), 0.0);
+        // integer exponents are exact
+        assert_eq!(pow(10.0, 22.0), 1e22);
+        assert_eq!(pow(2.0, -3.0), 0.125);
+        // negative bases give the principal complex value
+        let root = pow(-8.0, 1.0 / 3.0);
+        assert!((root.re - 1.0).abs() < 1e-12 && (root.im - 3f64.sqrt()).abs() < 1e-12);
+        let root = pow(-4.0, 0.5);
+        assert_eq!((root.re, root.im), (0.0, 2.0));
+        // overflow gives an infinity rather than a wrapped value
+        assert_eq!(pow(2.0, 1024.0), f64::INFINITY);
+        assert_eq!(pow(-2.0, 1025.0), f64::NEG_INFINITY);
+        assert_eq!(pow(1.5, 3000.5), f64::INFINITY);
+    }
+
+    #[test]
+    #[should_panic(expected = "Overflow happened while raising the units of '2m' to the power of 200.")]
+    fn pow_rejects_overflowing_unit_exponents() {
+        Quantity::with_unit(2.0, unit("m")).pow(&Quantity::real(200.0));
+    }
+
+    #[test]
+    fn is_integer_needs_an_exact_real_integer() {
+        assert!(Quantity::real(3.0).is_integer());
+        assert!(Quantity::real(-2.0).is_integer());
+        assert!(Quantity::with_unit(3.0, unit("m")).is_integer());
+        assert!(!Quantity::real(2.5).is_integer());
+        assert!(!Quantity::real(f64::INFINITY).is_integer());
+        assert!(!Quantity::real(f64::NAN).is_integer());
+        assert!(!Quantity { im: 1.0, ..Quantity::real(3.0) }.is_integer());
+        assert!(!Quantity::with_uncertainty(3.0, 0.1).is_integer());
+    }
+
+    #[test]
+    fn as_index_accepts_non_zero_unitless_integers() {
+        assert_eq!(Quantity::real(2.0).as_index(), Ok(2));
+        assert_eq!(Quantity::real(-1.0).as_index(), Ok(-1));
+        let message = "Matrix indices are 1-based, so only pure, unitless, integer, non zero values are allowed when indexing a matrix (negative values count from the end)";
+        assert_eq!(Quantity::real(0.0).as_index(), Err(format!("{message} but '0' was found.")));
+        assert_eq!(Quantity::real(1.5).as_index(), Err(format!("{message} but '1.5' was found.")));
+        assert_eq!(Quantity::with_unit(1.0, unit("m")).as_index(), Err(format!("{message} but '1m' was found.")));
+        assert_eq!(Quantity { im: 1.0, ..Quantity::real(1.0) }.as_index(), Err(format!("{message} but '1 + 1i' was found.")));
+        assert!(Quantity::with_uncertainty(1.0, 0.5).as_index().is_err());
+        assert!(Quantity::real(1e19).as_index().is_err());
+    }
+
+    #[test]
+    fn deca_and_deci_prefixes_are_told_apart() {
+        let metre = Unit { metre: 1, ..Unit::unitless() };
+        let cubic_metre = Unit { metre: 3, ..Unit::unitless() };
+        assert_eq!(Unit::parse("daL"), Ok((cubic_metre.clone(), 0.01, 0.0)));
+        assert_eq!(Unit::parse("dL"), Ok((cubic_metre, 0.0001, 0.0)));
+        assert_eq!(Unit::parse("dam"), Ok((metre.clone(), 10.0, 0.0)));
+        assert_eq!(Unit::parse("dm"), Ok((metre, 0.1, 0.0)));
+        // 'deg' is the degree, not a prefixed unit
+        assert_eq!(Unit::parse("deg"), Ok((Unit::unitless(), std::f64::consts::PI / 180.0, 0.0)));
+        assert_eq!(Unit::parse("da").unwrap_err().message, "Unknown unit expression 'da' because the prefix 'da' is not followed by a unit");
+        assert_eq!(Unit::parse("d").unwrap_err().message, "Unknown unit expression 'd' because the prefix 'd' is not followed by a unit");
+    }
+
+    #[test]
+    fn prefixed_litres_are_cubic_metres() {
+        let cubic_metre = Unit { metre: 3, ..Unit::unitless() };
+        for (text, factor) in [("L", 1e-3), ("kL", 1.0), ("dL", 1e-4), ("cL", 1e-5), ("mL", 1e-6), ("µL", 1e-9)] {
+            assert_eq!(Unit::parse(text), Ok((cubic_metre.clone(), factor, 0.0)), "parsing '{text}'");
+        }
+    }
+
+    #[test]
+    fn grams_are_measured_in_kilograms() {
+        let kilogram = Unit { kilogram: 1, ..Unit::unitless() };
+        for (text, factor) in [("g", 1e-3), ("kg", 1.0), ("mg", 1e-6), ("µg", 1e-9), ("Mg", 1e3)] {
+            assert_eq!(Unit::parse(text), Ok((kilogram.clone(), factor, 0.0)), "parsing '{text}'");
+        }
+    }
+
+    #[test]
+    fn ratios_are_unitless_factors() {
+        for (text, factor) in [("%", 1e-2), ("‰", 1e-3), ("ppm", 1e-6), ("ppb", 1e-9)] {
+            assert_eq!(Unit::parse(text), Ok((Unit::unitless(), factor, 0.0)), "parsing '{text}'");
+        }
+    }
+
+    #[test]
+    fn astronomical_lengths_are_metres() {
+        let metre = Unit { metre: 1, ..Unit::unitless() };
+        assert_eq!(Unit::parse("AU"), Ok((metre.clone(), 149_597_870_700.0, 0.0)));
+        // the distance light travels in a Julian year
+        assert_eq!(Unit::parse("ly"), Ok((metre.clone(), 299_792_458.0 * 365.25 * 86400.0, 0.0)));
+        // the distance at which one AU subtends one arcsecond
+        let (unit, parsec, _) = Unit::parse("pc").unwrap();
+        assert_eq!(unit, metre);
+        assert!((parsec / (149_597_870_700.0 * 648_000.0 / std::f64::consts::PI) - 1.0).abs() < 1e-15);
+        // 'pC' is still the picocoulomb
+        assert_eq!(Unit::parse("pC"), Ok((Unit { ampere: 1, second: 1, ..Unit::unitless() }, 1e-12, 0.0)));
+    }
+
+    #[test]
+    fn pressures_are_pascals() {
+        let pascal = Unit { kilogram: 1, metre: -1, second: -2, ..Unit::unitless() };
+        for (text, factor) in [("Pa", 1.0), ("bar", 1e5), ("mbar", 1e2), ("atm", 101_325.0), ("mmHg", 133.322_387_415)] {
+            assert_eq!(Unit::parse(text), Ok((pascal.clone(), factor, 0.0)), "parsing '{text}'");
+        }
+    }
+
+    #[test]
+    fn calories_are_joules() {
+        let joule = Unit { kilogram: 1, metre: 2, second: -2, ..Unit::unitless() };
+        assert_eq!(Unit::parse("cal"), Ok((joule.clone(), 4.184, 0.0)));
+        assert_eq!(Unit::parse("kcal"), Ok((joule.clone(), 4184.0, 0.0)));
+        assert_eq!(Unit::parse("kcal/mol"), Ok((Unit { mole: -1, ..joule }, 4184.0, 0.0)));
+    }
+
+    #[test]
+    fn arguments_follow_the_negative_real_branch_cut() {
+        use std::f64::consts::PI;
+        let complex = |re: f64, im: f64| Quantity { re, im, vre: 0.0, vim: 0.0, unit: Unit::unitless() };
+        // -1 lies on the cut, which belongs to the upper half plane
+        assert_eq!(Quantity::real(-1.0).arg().re, PI);
+        assert_eq!(Quantity::real(-1.0).arg_positive().re, PI);
+        assert_eq!(complex(-1.0, -0.0).arg().re, PI);
+        assert_eq!(complex(0.0, -1.0).arg().re, -PI / 2.0);
+        assert_eq!(complex(0.0, -1.0).arg_positive().re, 3.0 * PI / 2.0);
+        assert_eq!(Quantity::real(1.0).arg_positive().re, 0.0);
+        // principal values
+        let root = Quantity::real(-1.0).nthroot(2);
+        assert!(root.re.abs() < 1e-15 && root.im == 1.0, "sqrt(-1) gave {root}");
+        let ln = Quantity::real(-1.0).ln();
+        assert_eq!((ln.re, ln.im), (0.0, PI));
+    }
+
+    #[test]
+    fn round_uncertainty_keeps_the_significant_figures_of_sigma() {
+        let rounded = |x: f64, sx: f64, figures: u32| {
+            let q = Quantity::with_uncertainty(x, sx).round_uncertainty(figures);
+            (q.re, q.sigma_re())
+        };
+        assert_eq!(rounded(12.34, 0.46, 1), (12.3, 0.5));
+        assert_eq!(rounded(12.34, 0.46, 2), (12.34, 0.46));
+        assert_eq!(rounded(9.876, 0.0234, 1), (9.88, 0.02));
+        assert_eq!(rounded(9.876, 0.0234, 2), (9.876, 0.023));
+        assert_eq!(rounded(1234.0, 56.0, 1), (1230.0, 60.0));
+        assert_eq!(rounded(1234.0, 56.0, 2), (1234.0, 56.0));
+        // rounding 0.096 up to 0.1 does not add a figure
+        assert_eq!(rounded(0.96, 0.096, 1), (1.0, 0.1));
+        // exact values are left as they are
+        assert_eq!(rounded(1.2345, 0.0, 1), (1.2345, 0.0));
+    }
+}
@@ -0,0 +1,142 @@
+use std::process::{Command, Output};
+
+// runs the interpreter with the given arguments and environment variables
+fn run(args: &[&str], vars: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tera_lang"))
+        .args(args)
+        .env("RUST_BACKTRACE", "0")
+        .envs(vars.iter().copied())
+        .output()
+        .expect("Unable to run the interpreter")
+}
+
+fn stdout_with_env(args: &[&str], vars: &[(&str, &str)]) -> String {
+    let output = run(args, vars);
+    assert!(output.status.success(), "{args:?} failed with: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn stdout(args: &[&str]) -> String {
+    stdout_with_env(args, &[])
+}
+
+// a path in the temporary directory that no other test uses
+fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("tera_lang_cli_{}_{name}", std::process::id()));
+    path.to_string_lossy().into_owned()
+}
+
+// runs a script written to a temporary file and returns the first line it prints
+fn script(name: &str, code: &str, args: &[&str], vars: &[(&str, &str)]) -> String {
+    let path = temp_path(name);
+    std::fs::write(&path, code).unwrap();
+    let mut all_args = vec![&path[..]];
+    all_args.extend_from_slice(args);
+    let output = stdout_with_env(&all_args, vars);
+    std::fs::remove_file(&path).unwrap();
+    output.lines().next().unwrap_or_default().to_string()
+}
+
+#[test]
+fn the_remaining_arguments_are_passed_to_the_script() {
+    assert_eq!(script("args.tera", "print(args)", &["x", "b c"], &[]), "Matrix 2×1: [\"x\"; \"b c\"]");
+    // without arguments 'args' is an empty vector
+    assert_eq!(script("no_args.tera", "print(args)", &[], &[]), "Matrix 0×1: []");
+}
+
+#[test]
+fn env_reads_environment_variables() {
+    let vars = [("TERA_LANG_TEST_VAR", "hello")];
+    assert_eq!(script("env.tera", "print(env(\"TERA_LANG_TEST_VAR\"))", &[], &vars), "hello");
+    assert_eq!(script("env_default.tera", "print(env(\"TERA_LANG_TEST_VAR\", \"default\"))", &[], &vars), "hello");
+    // unset variables give the default or nothing
+    assert_eq!(script("env_unset.tera", "print(env(\"TERA_LANG_UNSET_VAR\", \"default\"))", &[], &[]), "default");
+    assert_eq!(script("env_void.tera", "print(type(env(\"TERA_LANG_UNSET_VAR\")))", &[], &[]), "Void");
+}
+
+#[test]
+fn debug_prints_the_internal_representation_to_stderr() {
+    let path = temp_path("debug.tera");
+    std::fs::write(&path, "print(debug(3 pm 1) + 1)").unwrap();
+    let output = run(&[&path], &[]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("4 ± 1\n"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("Number(Quantity { re: 3.0, im: 0.0, vre: 1.0, vim: 0.0, unit: Unit {"), "unexpected stderr: {stderr}");
+}
+
+#[test]
+fn range_loops_print_each_value() {
+    let path = temp_path("range_loop.tera");
+    std::fs::write(&path, "for i in range(1, 4) { print(i) }").unwrap();
+    let output = stdout_with_env(&[&path], &[]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.starts_with("1\n2\n3\n"), "unexpected output: {output}");
+}
+
+#[test]
+fn print_separates_its_arguments_without_a_trailing_space() {
+    assert_eq!(script("print_two.tera", "print(1, 2)", &[], &[]), "1 2");
+    assert_eq!(script("print_unit.tera", "print(\"a\", 1|m|)", &[], &[]), "a 1m");
+    // write does not end the line
+    assert_eq!(script("write.tera", "{ write(1, 2); print(\"\") }", &[], &[]), "12");
+}
+
+#[test]
+fn eval_runs_inline_code() {
+    assert_eq!(stdout(&["--eval", "2|m| + 3|cm|"]), "2.03m\n");
+    assert_eq!(stdout(&["-e", "2 + 3"]), "5\n");
+    let output = run(&["-e"], &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("The '-e' flag must be followed by the code to run"));
+}
+
+#[test]
+fn quiet_leaves_only_the_program_output() {
+    let path = temp_path("quiet.tera");
+    std::fs::write(&path, "{ print(\"hi\"); print(args) }").unwrap();
+    // the timing report goes to stderr
+    let output = run(&[&path], &[]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\nMatrix 0×1: []\n");
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("Running took "));
+    for args in [vec!["--quiet", &path], vec!["-q", &path], vec![&path, "-q"]] {
+        let output = run(&args, &[]);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\nMatrix 0×1: []\n");
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+    }
+    // the flag can be placed among the arguments of the script, '--' ends the flags
+    assert_eq!(stdout(&[&path, "-q", "x"]), "hi\nMatrix 1×1: [\"x\"]\n");
+    assert_eq!(stdout(&["-q", &path, "--", "-q"]), "hi\nMatrix 1×1: [\"-q\"]\n");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn the_value_of_the_program_is_printed() {
+    let path = temp_path("result.tera");
+    std::fs::write(&path, "{ x = 2; 5|m| }").unwrap();
+    assert_eq!(stdout(&["-q", &path]), "5m\n");
+    std::fs::remove_file(&path).unwrap();
+    // programs without a value, or whose loops only print, are not printed twice
+    assert_eq!(stdout(&["-e", "print(1)"]), "1\n");
+    assert_eq!(stdout(&["-e", "for i in range(1, 3) { print(i) }"]), "1\n2\n");
+    assert_eq!(stdout(&["-e", "{ x = 3; }"]), "");
+    // a final assignment is not printed, a final variable is
+    assert_eq!(stdout(&["-e", "x = 5"]), "");
+    assert_eq!(stdout(&["-e", "{ y = 1; x = y + 1 }"]), "");
+    assert_eq!(stdout(&["-e", "{ x = 2; x }"]), "2\n");
+}
+
+#[test]
+fn void_values_print_no_text() {
+    assert_eq!(stdout(&["-e", "{ print(if 0 {1}); 3 }"]), "\n3\n");
+    assert_eq!(stdout(&["-e", "print(1, if 0 {1}, 2)"]), "1 2\n");
+    assert_eq!(stdout(&["-e", "write(if 0 {1})"]), "");
+    // the type is still reported
+    assert_eq!(stdout(&["-e", "type(if 0 {1})"]), "Void\n");
+}
+
+#[test]
+fn for_loops_print_positions_alongside_values() {
+    assert_eq!(stdout(&["-e", "for i, x in [10, 20, 30] { print(i, x) }"]), "1 10\n2 20\n3 30\n");
+}